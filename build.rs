@@ -0,0 +1,17 @@
+const COMMANDS: &[&str] = &[
+    "clear_cache",
+    "parse_shortcut",
+    "fetch_link",
+    "fetch_links",
+    "cancel_fetch",
+    "check_link",
+    "prefetch",
+    "reset_seen_urls",
+    "subscribe",
+    "supported_extensions",
+    "coalesce_window",
+];
+
+fn main() {
+    tauri_plugin::Builder::new(COMMANDS).build();
+}
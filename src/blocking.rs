@@ -0,0 +1,48 @@
+use std::io::Read;
+
+use reqwest::blocking::Client;
+
+use crate::extract::{extract, ExtractConfig};
+use crate::{LinkMetadata, LinkdropError, DEFAULT_MAX_BODY_SIZE, DEFAULT_TIMEOUT, DEFAULT_USER_AGENT};
+
+/// Blocking counterpart of [`crate::fetch_metadata`], for callers outside an
+/// async runtime. Uses the default timeout, user agent and body size limit and
+/// the same extraction as the async path, but doesn't retry `429`s or check
+/// for `/favicon.ico`. Must not be called from within an async runtime.
+pub fn fetch_metadata_blocking(url: &str) -> Result<LinkMetadata, LinkdropError> {
+    let client = Client::builder()
+        .timeout(DEFAULT_TIMEOUT)
+        .user_agent(DEFAULT_USER_AGENT)
+        .build()?;
+    let resp = client.get(url).send()?;
+    let limit = DEFAULT_MAX_BODY_SIZE;
+    if let Some(length) = resp.content_length().filter(|&length| length > limit) {
+        return Err(LinkdropError::TooLarge { limit, length });
+    }
+    let base_url = resp.url().clone();
+    let mut body = Vec::new();
+    resp.take(limit).read_to_end(&mut body)?;
+    let html = String::from_utf8_lossy(&body);
+    Ok(extract(url, &base_url, &html, &ExtractConfig::default()).metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::matchers::path;
+    use wiremock::{Mock, MockServer};
+
+    use super::*;
+    use crate::tests::page;
+
+    #[test]
+    fn fetches_outside_an_async_runtime() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let server = runtime.block_on(async {
+            let server = MockServer::start().await;
+            Mock::given(path("/")).respond_with(page("<title>Blocking</title>")).mount(&server).await;
+            server
+        });
+        let meta = fetch_metadata_blocking(&server.uri()).unwrap();
+        assert_eq!(meta.title.as_deref(), Some("Blocking"));
+    }
+}
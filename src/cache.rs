@@ -0,0 +1,211 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::LinkMetadata;
+
+/// How often inserts trigger a write of the persisted cache file.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+const CACHE_FILE_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct CacheEntry {
+    pub metadata: LinkMetadata,
+    pub etag: Option<String>,
+    /// Unix timestamp (milliseconds) of the last successful fetch or
+    /// revalidation.
+    pub fetched_at: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    entries: HashMap<String, CacheEntry>,
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: HashMap<String, CacheEntry>,
+    /// Counts changes to `entries`; `saved` is the count last written to disk.
+    changes: u64,
+    saved: u64,
+    last_flush: Option<SystemTime>,
+}
+
+/// In-memory metadata cache keyed by the link's URL as given, before any
+/// rewrite, normalized with `normalize_url`. Optionally backed by a JSON file.
+pub(crate) struct MetadataCache {
+    ttl: Duration,
+    path: Option<PathBuf>,
+    inner: Mutex<Inner>,
+    /// Held while the file is written, so an older snapshot can't overwrite a
+    /// newer one. `inner` isn't held meanwhile, so lookups don't wait on disk.
+    writing: Mutex<()>,
+}
+
+impl MetadataCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            path: None,
+            inner: Mutex::new(Inner::default()),
+            writing: Mutex::new(()),
+        }
+    }
+
+    /// Create a cache persisted at `path`, loading any entries already there.
+    /// A missing or unreadable file starts the cache empty.
+    pub fn persistent(ttl: Duration, path: PathBuf) -> Self {
+        let entries = fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<CacheFile>(&bytes).ok())
+            .filter(|file| file.version == CACHE_FILE_VERSION)
+            .map(|file| file.entries)
+            .unwrap_or_default();
+        Self {
+            ttl,
+            path: Some(path),
+            inner: Mutex::new(Inner {
+                entries,
+                ..Inner::default()
+            }),
+            writing: Mutex::new(()),
+        }
+    }
+
+    pub fn get(&self, url: &str) -> Option<CacheEntry> {
+        self.inner.lock().unwrap().entries.get(url).cloned()
+    }
+
+    pub fn is_fresh(&self, entry: &CacheEntry) -> bool {
+        now().saturating_sub(Duration::from_millis(entry.fetched_at)) < self.ttl
+    }
+
+    pub fn insert(&self, url: &str, metadata: LinkMetadata, etag: Option<String>) {
+        let due = {
+            let mut inner = self.inner.lock().unwrap();
+            inner.entries.insert(
+                url.to_string(),
+                CacheEntry {
+                    metadata,
+                    etag,
+                    fetched_at: now().as_millis() as u64,
+                },
+            );
+            inner.changes += 1;
+            inner
+                .last_flush
+                .and_then(|at| at.elapsed().ok())
+                .is_none_or(|elapsed| elapsed >= FLUSH_INTERVAL)
+        };
+        if due {
+            self.flush();
+        }
+    }
+
+    pub fn remove(&self, url: &str) {
+        let removed = {
+            let mut inner = self.inner.lock().unwrap();
+            let removed = inner.entries.remove(url).is_some();
+            inner.changes += u64::from(removed);
+            removed
+        };
+        if removed {
+            self.flush();
+        }
+    }
+
+    pub fn clear(&self) {
+        {
+            let mut inner = self.inner.lock().unwrap();
+            inner.entries.clear();
+            inner.changes += 1;
+        }
+        self.flush();
+    }
+
+    /// Write pending changes to disk, if this cache is persistent.
+    pub fn flush(&self) {
+        let Some(path) = &self.path else { return };
+        let _writing = self.writing.lock().unwrap();
+        let (file, changes) = {
+            let mut inner = self.inner.lock().unwrap();
+            if inner.changes == inner.saved {
+                return;
+            }
+            inner.last_flush = Some(SystemTime::now());
+            let file = CacheFile {
+                version: CACHE_FILE_VERSION,
+                entries: inner.entries.clone(),
+            };
+            (file, inner.changes)
+        };
+        let written = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(path, serde_json::to_vec(&file)?));
+        if written.is_ok() {
+            self.inner.lock().unwrap().saved = changes;
+        }
+    }
+}
+
+/// Time since the Unix epoch.
+fn now() -> Duration {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(url: &str) -> LinkMetadata {
+        LinkMetadata {
+            url: url.to_string(),
+            title: Some("Cached".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn persisted_entries_survive_a_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("linkdrop").join("metadata-cache.json");
+        let cache = MetadataCache::persistent(Duration::from_secs(3600), path.clone());
+        cache.insert("https://example.com/a", metadata("https://example.com/a"), Some("\"a\"".to_string()));
+        cache.insert("https://example.com/b", metadata("https://example.com/b"), None);
+        cache.flush();
+
+        let reloaded = MetadataCache::persistent(Duration::from_secs(3600), path.clone());
+        let entry = reloaded.get("https://example.com/a").unwrap();
+        assert_eq!(entry.metadata.title.as_deref(), Some("Cached"));
+        assert_eq!(entry.etag.as_deref(), Some("\"a\""));
+        assert!(reloaded.is_fresh(&entry));
+        assert!(reloaded.get("https://example.com/b").is_some());
+
+        reloaded.remove("https://example.com/a");
+        let reloaded = MetadataCache::persistent(Duration::from_secs(3600), path.clone());
+        assert!(reloaded.get("https://example.com/a").is_none());
+        assert!(reloaded.get("https://example.com/b").is_some());
+
+        fs::write(&path, "not json").unwrap();
+        assert!(MetadataCache::persistent(Duration::from_secs(3600), path).get("https://example.com/b").is_none());
+    }
+
+    #[test]
+    fn sub_second_ttl_expires() {
+        let cache = MetadataCache::new(Duration::from_millis(50));
+        cache.insert("https://example.com/", metadata("https://example.com/"), None);
+        let entry = cache.get("https://example.com/").unwrap();
+        assert!(cache.is_fresh(&entry));
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(!cache.is_fresh(&entry));
+    }
+}
@@ -0,0 +1,493 @@
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use futures::{stream, StreamExt};
+use reqwest::{header::RANGE, StatusCode};
+use serde::Serialize;
+use tauri::{ipc::Channel, AppHandle, Manager, Runtime, State};
+
+use crate::{
+    audit, execute, extract::normalize_url, parse_link, resolve_link, LinkMetadata, Linkdrop, LinkdropError,
+    MAX_COALESCE_WINDOW,
+};
+
+/// How many of a `fetch_links` batch's URLs are fetched at once.
+const BATCH_CONCURRENCY: usize = 8;
+
+/// Payload of `link-fetched`.
+#[derive(Serialize, Clone)]
+struct FetchResult {
+    id: u64,
+    /// URL-only metadata when the fetch failed. `url` is the candidate the
+    /// metadata came from.
+    metadata: LinkMetadata,
+    /// Why the last candidate failed, when none succeeded.
+    error: Option<String>,
+    /// Candidates tried and failed before the one in `metadata`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    failed: Vec<FailedCandidate>,
+}
+
+#[derive(Serialize, Clone)]
+struct FailedCandidate {
+    url: String,
+    error: String,
+}
+
+/// Result of `check_link`.
+#[derive(Serialize)]
+pub(crate) struct LinkStatus {
+    reachable: bool,
+    /// Status of the final response after redirects; `null` when no response
+    /// arrived at all.
+    status: Option<u16>,
+}
+
+/// Payload of `link-prefetch-progress`.
+#[derive(Serialize, Clone)]
+struct PrefetchProgress {
+    url: String,
+    /// How many of the prefetch's URLs are done, this one included.
+    completed: usize,
+    total: usize,
+    error: Option<String>,
+}
+
+/// Payload of `link-batch-progress`.
+#[derive(Serialize, Clone)]
+struct BatchProgress {
+    batch_id: u64,
+    /// How many of the batch's URLs are done, this one included.
+    completed: usize,
+    total: usize,
+    /// URL-only when the fetch failed.
+    metadata: LinkMetadata,
+    error: Option<String>,
+}
+
+/// Payload of `link-fetch-cancelled`.
+#[derive(Serialize, Clone)]
+struct FetchCancelled {
+    id: u64,
+}
+
+/// `plugin:linkdrop|clear_cache` — evict `url` from the metadata cache, or
+/// clear the whole cache when no `url` is given. `url` is the link as dropped or
+/// passed to a fetch command, before `Builder::rewrite_url`; spelling
+/// differences such as host case or a fragment don't matter. Persisted caches
+/// are rewritten immediately. Does nothing when caching is disabled.
+#[tauri::command]
+pub(crate) fn clear_cache(state: State<'_, Linkdrop>, url: Option<String>) {
+    if let Some(cache) = &state.cache {
+        match url {
+            Some(url) => cache.remove(&normalize_url(url)),
+            None => cache.clear(),
+        }
+    }
+}
+
+/// `plugin:linkdrop|parse_shortcut` — resolve the URL a dropped shortcut file
+/// points to without fetching anything. Rejects with the error message as a
+/// string when the file can't be read or holds no URL.
+#[tauri::command]
+pub(crate) fn parse_shortcut(state: State<'_, Linkdrop>, path: PathBuf) -> Result<String, String> {
+    crate::read_shortcut(&path, state.drop.max_shortcut_size).map_err(|e| e.to_string())
+}
+
+/// `plugin:linkdrop|fetch_link` — start fetching metadata for `url` the same
+/// way a dropped link is, and return an id right away. The result arrives as a
+/// `link-fetched` event carrying `{ id, metadata, error }`.
+///
+/// `mirrors` are tried in order, only if `url` fails, and the first success
+/// wins. Each candidate that failed before it is listed in `failed`; if all
+/// fail, `error` is the last one's and `metadata` is URL-only for it.
+#[tauri::command]
+pub(crate) fn fetch_link<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, Linkdrop>,
+    url: String,
+    mirrors: Option<Vec<String>>,
+) -> u64 {
+    let id = state.next_fetch_id.fetch_add(1, Ordering::Relaxed);
+    let mut fetches = state.fetches.lock().unwrap();
+    let task = tauri::async_runtime::spawn(async move {
+        let state = app.state::<Linkdrop>();
+        let mut candidates = std::iter::once(url).chain(mirrors.unwrap_or_default()).peekable();
+        let mut failed = Vec::new();
+        let mut result = None;
+        while let Some(url) = candidates.next() {
+            match resolve_link(&state, &url).await {
+                Ok(metadata) => {
+                    result = Some(FetchResult { id, metadata, error: None, failed });
+                    break;
+                }
+                Err(e) if candidates.peek().is_none() => {
+                    result = Some(FetchResult {
+                        id,
+                        error: Some(e.to_string()),
+                        metadata: LinkMetadata::fallback(url, &e),
+                        failed,
+                    });
+                    break;
+                }
+                Err(e) => failed.push(FailedCandidate { url, error: e.to_string() }),
+            }
+        }
+        let result = result.expect("there is always at least one candidate");
+        state.fetches.lock().unwrap().remove(&id);
+        state.emit(&app, "link-fetched", result);
+    });
+    fetches.insert(id, task);
+    id
+}
+
+/// `plugin:linkdrop|fetch_links` — fetch every URL in `urls`, up to eight at
+/// a time, and resolve with their metadata in the same order (URL-only for
+/// failures). Emits `link-batch-progress` with `{ batch_id, completed, total,
+/// metadata, error }` as each one finishes, `completed` counting up from 1;
+/// `batch_id` is the caller's, to tell concurrent batches apart. Keys are
+/// spelled per `Builder::field_naming`, like the events'.
+#[tauri::command]
+pub(crate) async fn fetch_links<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, Linkdrop>,
+    batch_id: u64,
+    urls: Vec<String>,
+) -> Result<serde_json::Value, String> {
+    let total = urls.len();
+    let mut results = vec![LinkMetadata::default(); total];
+    let state = &*state;
+    let mut fetches = stream::iter(urls.into_iter().enumerate())
+        .map(|(i, url)| async move {
+            match resolve_link(state, &url).await {
+                Ok(metadata) => (i, metadata, None),
+                Err(e) => (i, LinkMetadata::fallback(url, &e), Some(e.to_string())),
+            }
+        })
+        .buffer_unordered(BATCH_CONCURRENCY);
+    let mut completed = 0;
+    while let Some((i, metadata, error)) = fetches.next().await {
+        completed += 1;
+        let progress = BatchProgress { batch_id, completed, total, metadata, error };
+        state.emit(&app, "link-batch-progress", &progress);
+        results[i] = progress.metadata;
+    }
+    state.to_json(results).map_err(|e| e.to_string())
+}
+
+/// `plugin:linkdrop|cancel_fetch` — abort the `fetch_link` call with this id
+/// and emit `link-fetch-cancelled` with `{ id }`. Returns `false` if the fetch
+/// already finished or the id is unknown.
+#[tauri::command]
+pub(crate) fn cancel_fetch<R: Runtime>(app: AppHandle<R>, state: State<'_, Linkdrop>, id: u64) -> bool {
+    let Some(task) = state.fetches.lock().unwrap().remove(&id) else {
+        return false;
+    };
+    task.abort();
+    state.emit(&app, "link-fetch-cancelled", FetchCancelled { id });
+    true
+}
+
+/// `plugin:linkdrop|check_link` — report whether `url` answers with a success
+/// status, without downloading or parsing the page. Sends a `HEAD`, falling back
+/// to a single-byte ranged `GET` for servers that reject `HEAD`, with the
+/// configured timeout, `Builder::basic_auth` credentials and
+/// `Builder::on_response` auditing. Rejects only when `url` isn't an http(s)
+/// URL or its host is blocked by `Builder::allow_host`/`Builder::deny_host`.
+#[tauri::command]
+pub(crate) async fn check_link(state: State<'_, Linkdrop>, url: String) -> Result<LinkStatus, String> {
+    let url = parse_link(&url).map_err(|e| e.to_string())?;
+    let host = url.host_str().unwrap_or_default();
+    if !state.fetch.hosts.permits(host) {
+        return Err(LinkdropError::Blocked(host.to_string()).to_string());
+    }
+    let send = async |req| {
+        let resp = execute(req, &state.fetch).await;
+        if let Ok(resp) = &resp {
+            audit(resp, 0, &state.fetch);
+        }
+        resp
+    };
+    let mut resp = send(state.client.head(url.clone())).await;
+    let rejects_head = |status| matches!(status, StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED);
+    if matches!(&resp, Ok(r) if rejects_head(r.status())) {
+        resp = send(state.client.get(url).header(RANGE, "bytes=0-0")).await;
+    }
+    let status = resp.ok().map(|r| r.status());
+    Ok(LinkStatus {
+        reachable: status.is_some_and(|s| s.is_success()),
+        status: status.map(|s| s.as_u16()),
+    })
+}
+
+/// `plugin:linkdrop|prefetch` — resolve `urls` in the background so later
+/// drops and `fetch_link` calls are answered from the cache. Returns at once;
+/// each URL emits `link-prefetch-progress` with `{ url, completed, total,
+/// error }` as it finishes. Fetches go through the worker pool when one is
+/// configured, and otherwise run up to eight at a time like `fetch_links`.
+/// Only useful with a cache enabled.
+#[tauri::command]
+pub(crate) fn prefetch<R: Runtime>(app: AppHandle<R>, urls: Vec<String>) {
+    let total = urls.len();
+    let completed = Arc::new(AtomicUsize::new(0));
+    if app.state::<Linkdrop>().pool.is_none() {
+        tauri::async_runtime::spawn(async move {
+            stream::iter(urls)
+                .for_each_concurrent(BATCH_CONCURRENCY, |url| prefetch_one(&app, url, &completed, total))
+                .await;
+        });
+        return;
+    }
+    // Submitting to a full worker pool blocks, so don't do it on the caller's thread.
+    std::thread::spawn(move || {
+        let state = app.state::<Linkdrop>();
+        for url in urls {
+            let app = app.clone();
+            let completed = completed.clone();
+            state.run(async move { prefetch_one(&app, url, &completed, total).await });
+        }
+    });
+}
+
+/// Resolve one of a prefetch's `total` URLs and report it done.
+async fn prefetch_one<R: Runtime>(app: &AppHandle<R>, url: String, completed: &AtomicUsize, total: usize) {
+    let state = app.state::<Linkdrop>();
+    let error = resolve_link(&state, &url).await.err().map(|e| e.to_string());
+    let completed = completed.fetch_add(1, Ordering::Relaxed) + 1;
+    state.emit(app, "link-prefetch-progress", PrefetchProgress { url, completed, total, error });
+}
+
+/// `plugin:linkdrop|reset_seen_urls` — forget the URLs remembered for
+/// `Builder::skip_seen_urls`, so each may be processed once more.
+#[tauri::command]
+pub(crate) fn reset_seen_urls(state: State<'_, Linkdrop>) {
+    if let Some(seen) = &state.seen {
+        seen.clear();
+    }
+}
+
+/// `plugin:linkdrop|subscribe` — register `channel` to receive every event as
+/// `{ event, payload }` when `Builder::delivery` includes channels.
+#[tauri::command]
+pub(crate) fn subscribe(state: State<'_, Linkdrop>, channel: Channel<serde_json::Value>) {
+    state.channels.lock().unwrap().push(channel);
+}
+
+/// `plugin:linkdrop|coalesce_window` — the drop coalescing window in
+/// milliseconds, after changing it to `ms` (at most 5000) if given. Gestures
+/// already waiting keep the window they started with.
+#[tauri::command]
+pub(crate) fn coalesce_window(state: State<'_, Linkdrop>, ms: Option<u64>) -> u64 {
+    if let Some(ms) = ms {
+        let ms = ms.min(MAX_COALESCE_WINDOW.as_millis() as u64);
+        state.coalesce_window_ms.store(ms, Ordering::Relaxed);
+    }
+    state.coalesce_window_ms.load(Ordering::Relaxed)
+}
+
+/// `plugin:linkdrop|supported_extensions` — the extensions, lowercase and
+/// without the dot, of dropped files the plugin treats as links.
+#[tauri::command]
+pub(crate) fn supported_extensions(state: State<'_, Linkdrop>) -> Vec<String> {
+    state.drop.extensions().into_iter().map(String::from).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+    use crate::tests::{mock_app, page, record, wait_for};
+    use crate::Builder;
+
+    #[tokio::test]
+    async fn cancelled_fetch_stops_promptly() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(page("<title>Slow</title>").set_delay(Duration::from_millis(500)))
+            .mount(&server)
+            .await;
+        let app = mock_app(Builder::new());
+        let fetched = record(&app, "link-fetched");
+        let cancelled = record(&app, "link-fetch-cancelled");
+
+        let id = fetch_link(app.handle().clone(), app.state(), server.uri(), None);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(cancel_fetch(app.handle().clone(), app.state(), id));
+        assert_eq!(wait_for(&cancelled, 1).await[0]["id"], id);
+
+        // Long enough for the response to have arrived had the fetch gone on.
+        tokio::time::sleep(Duration::from_millis(800)).await;
+        assert!(fetched.lock().unwrap().is_empty());
+        assert!(!cancel_fetch(app.handle().clone(), app.state(), id));
+    }
+
+    #[test]
+    fn coalesce_window_command_reads_and_clamps() {
+        let app = mock_app(Builder::new());
+        assert_eq!(coalesce_window(app.state(), None), 150);
+        assert_eq!(coalesce_window(app.state(), Some(40)), 40);
+        assert_eq!(coalesce_window(app.state(), None), 40);
+        assert_eq!(coalesce_window(app.state(), Some(u64::MAX)), 5000);
+    }
+
+    #[tokio::test]
+    async fn check_link_reports_live_and_dead_links() {
+        let server = MockServer::start().await;
+        Mock::given(path("/live")).respond_with(ResponseTemplate::new(200)).mount(&server).await;
+        Mock::given(method("HEAD"))
+            .and(path("/no-head"))
+            .respond_with(ResponseTemplate::new(405))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET")).and(path("/no-head")).respond_with(ResponseTemplate::new(206)).mount(&server).await;
+        let app = mock_app(Builder::new());
+        let check = |path: &str| check_link(app.state(), format!("{}{path}", server.uri()));
+
+        let live = check("/live").await.unwrap();
+        assert!(live.reachable);
+        assert_eq!(live.status, Some(200));
+        let dead = check("/dead").await.unwrap();
+        assert!(!dead.reachable);
+        assert_eq!(dead.status, Some(404));
+        let no_head = check("/no-head").await.unwrap();
+        assert_eq!((no_head.reachable, no_head.status), (true, Some(206)));
+
+        // Nothing listens on port 9 of this host.
+        let down = check_link(app.state(), "http://127.0.0.1:9/".to_string()).await.unwrap();
+        assert_eq!((down.reachable, down.status), (false, None));
+        assert!(check_link(app.state(), "ftp://example.com/".to_string()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn basic_auth_credentials_reach_only_their_host() {
+        let server = MockServer::start().await;
+        // `user:secret`
+        Mock::given(header("authorization", "Basic dXNlcjpzZWNyZXQ="))
+            .respond_with(page("<title>Private docs</title>"))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET")).respond_with(ResponseTemplate::new(401)).mount(&server).await;
+        Mock::given(method("HEAD")).respond_with(ResponseTemplate::new(401)).mount(&server).await;
+        let audits = Arc::new(AtomicUsize::new(0));
+        let counter = audits.clone();
+        let builder = Builder::new()
+            .basic_auth("127.0.0.1", "user", Some("secret".to_string()))
+            .resolve("elsewhere.example.test", *server.address())
+            .on_response(move |_| {
+                counter.fetch_add(1, Ordering::Relaxed);
+            });
+        let app = mock_app(builder);
+        let url = format!("{}/docs", server.uri());
+
+        let meta = resolve_link(&app.state::<Linkdrop>(), &url).await.unwrap();
+        assert_eq!(meta.title.as_deref(), Some("Private docs"));
+        let before = audits.load(Ordering::Relaxed);
+        let status = check_link(app.state(), url).await.unwrap();
+        assert_eq!((status.reachable, status.status), (true, Some(200)));
+        assert_eq!(audits.load(Ordering::Relaxed), before + 1);
+
+        let elsewhere = format!("http://elsewhere.example.test:{}/docs", server.address().port());
+        let status = check_link(app.state(), elsewhere).await.unwrap();
+        assert_eq!(status.status, Some(401));
+        let requests = server.received_requests().await.unwrap();
+        let leaked = requests
+            .iter()
+            .filter(|request| request.headers.get("host").is_some_and(|host| host.to_str().unwrap().starts_with("elsewhere")))
+            .any(|request| request.headers.contains_key("authorization"));
+        assert!(!leaked);
+    }
+
+    #[tokio::test]
+    async fn fetch_links_results_follow_field_naming() {
+        let server = MockServer::start().await;
+        let html = r#"<title>Page</title><link rel="canonical" href="/canonical">"#;
+        Mock::given(path("/")).respond_with(page(html)).mount(&server).await;
+        let app = mock_app(Builder::new().field_naming(crate::FieldNaming::CamelCase));
+        let progress = record(&app, "link-batch-progress");
+
+        let results = fetch_links(app.handle().clone(), app.state(), 1, vec![server.uri()]).await.unwrap();
+        assert_eq!(results[0]["canonicalUrl"], format!("{}/canonical", server.uri()));
+        assert!(results[0].get("canonical_url").is_none());
+        assert_eq!(wait_for(&progress, 1).await[0]["batchId"], 1);
+    }
+
+    #[tokio::test]
+    async fn prefetch_fills_the_cache_a_few_at_a_time() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(page("<title>Saved</title>").set_delay(Duration::from_millis(300)))
+            .mount(&server)
+            .await;
+        let app = mock_app(Builder::new().cache_ttl(Duration::from_secs(60)).favicon_fallback(false));
+        let progress = record(&app, "link-prefetch-progress");
+        let urls: Vec<_> = (0..2 * BATCH_CONCURRENCY).map(|i| format!("{}/{i}", server.uri())).collect();
+
+        let started = std::time::Instant::now();
+        prefetch(app.handle().clone(), urls.clone());
+        let events = wait_for(&progress, urls.len()).await;
+        // Two rounds of slow responses; all at once would take one.
+        assert!(started.elapsed() >= Duration::from_millis(600));
+        assert_eq!(events.last().unwrap()["completed"], urls.len());
+        assert!(events.iter().all(|event| event["error"].is_null()));
+
+        let state = app.state::<Linkdrop>();
+        let meta = resolve_link(&state, &urls[3]).await.unwrap();
+        assert_eq!(meta.title.as_deref(), Some("Saved"));
+        assert_eq!(server.received_requests().await.unwrap().len(), urls.len());
+    }
+
+    #[tokio::test]
+    async fn fetch_link_falls_back_to_mirrors() {
+        let server = MockServer::start().await;
+        Mock::given(path("/mirror")).respond_with(page("<title>Mirror</title>")).mount(&server).await;
+        let app = mock_app(Builder::new());
+        let fetched = record(&app, "link-fetched");
+        let mirror = format!("{}/mirror", server.uri());
+
+        let primary = "http://127.0.0.1:9/".to_string();
+        let id = fetch_link(app.handle().clone(), app.state(), primary.clone(), Some(vec![mirror.clone()]));
+        let result = wait_for(&fetched, 1).await.remove(0);
+        assert_eq!(result["id"], id);
+        assert_eq!(result["metadata"]["url"], mirror);
+        assert_eq!(result["metadata"]["title"], "Mirror");
+        assert!(result["error"].is_null());
+        assert_eq!(result["failed"][0]["url"], primary);
+    }
+
+    #[tokio::test]
+    async fn fetch_links_reports_progress_in_order() {
+        let server = MockServer::start().await;
+        for (i, delay) in [300, 0, 150].into_iter().enumerate() {
+            Mock::given(path(format!("/{i}")))
+                .respond_with(page(&format!("<title>Page {i}</title>")).set_delay(Duration::from_millis(delay)))
+                .mount(&server)
+                .await;
+        }
+        let app = mock_app(Builder::new());
+        let progress = record(&app, "link-batch-progress");
+        let urls: Vec<_> = (0..3).map(|i| format!("{}/{i}", server.uri())).collect();
+
+        let results = fetch_links(app.handle().clone(), app.state(), 42, urls.clone()).await.unwrap();
+        let titles: Vec<_> = results.as_array().unwrap().iter().map(|m| m["title"].as_str().unwrap()).collect();
+        assert_eq!(titles, ["Page 0", "Page 1", "Page 2"]);
+
+        let events = wait_for(&progress, 3).await;
+        for (n, event) in events.iter().enumerate() {
+            assert_eq!(event["batch_id"], 42);
+            assert_eq!(event["completed"], n + 1);
+            assert_eq!(event["total"], 3);
+        }
+        // Fastest first.
+        let finished: Vec<_> = events.iter().map(|e| e["metadata"]["url"].as_str().unwrap()).collect();
+        assert_eq!(finished, [&urls[1], &urls[2], &urls[0]]);
+    }
+}
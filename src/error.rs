@@ -0,0 +1,114 @@
+use std::{fmt, time::Duration};
+
+/// Why a link could not be resolved to metadata.
+#[derive(Debug)]
+pub enum LinkdropError {
+    /// The shortcut file could not be read.
+    Io(std::io::Error),
+    /// The shortcut file did not contain a usable URL.
+    ParseFailed(String),
+    /// The link is not an http(s) URL.
+    UnsupportedScheme(String),
+    /// The request failed or the server returned an unusable response.
+    Http(reqwest::Error),
+    /// The server advertised a body larger than the configured limit.
+    TooLarge { limit: u64, length: u64 },
+    /// The server kept answering `429 Too Many Requests`.
+    RateLimited { retry_after: Option<Duration> },
+    /// The page redirected more times than [`crate::Builder::max_redirects`]
+    /// allows.
+    TooManyRedirects(reqwest::Error),
+    /// Resolving the link took longer than [`crate::Builder::deadline`].
+    Timeout(Duration),
+    /// A redirect was refused by [`crate::Builder::redirect_filter`].
+    /// `chain` holds the URLs requested, ending with the refused target.
+    RedirectRefused { chain: Vec<String> },
+    /// The link's host is refused by [`crate::Builder::allow_host`] or
+    /// [`crate::Builder::deny_host`].
+    Blocked(String),
+}
+
+impl LinkdropError {
+    /// A stable snake_case name for the variant, e.g. `"rate_limited"`, for
+    /// frontends that branch on the kind of failure.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Io(_) => "io",
+            Self::ParseFailed(_) => "parse_failed",
+            Self::UnsupportedScheme(_) => "unsupported_scheme",
+            Self::Http(_) => "http",
+            Self::TooLarge { .. } => "too_large",
+            Self::RateLimited { .. } => "rate_limited",
+            Self::TooManyRedirects(_) => "too_many_redirects",
+            Self::Timeout(_) => "timeout",
+            Self::RedirectRefused { .. } => "redirect_refused",
+            Self::Blocked(_) => "blocked",
+        }
+    }
+}
+
+impl fmt::Display for LinkdropError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "failed to read shortcut file: {e}"),
+            Self::ParseFailed(reason) => write!(f, "failed to parse shortcut file: {reason}"),
+            Self::UnsupportedScheme(scheme) => write!(f, "unsupported URL scheme `{scheme}`"),
+            Self::Http(e) => write!(f, "request failed: {e}"),
+            Self::TooLarge { limit, length } => {
+                write!(f, "response body of {length} bytes exceeds the {limit} byte limit")
+            }
+            Self::RateLimited { retry_after: Some(wait) } => {
+                write!(f, "rate limited, retry after {} seconds", wait.as_secs())
+            }
+            Self::RateLimited { retry_after: None } => write!(f, "rate limited"),
+            Self::TooManyRedirects(e) => write!(f, "too many redirects: {e}"),
+            Self::Timeout(deadline) => write!(f, "gave up after {} seconds", deadline.as_secs_f32()),
+            Self::RedirectRefused { chain } => {
+                write!(f, "refused redirect to `{}`", chain.last().map_or("", String::as_str))
+            }
+            Self::Blocked(host) => write!(f, "fetching from `{host}` is not allowed"),
+        }
+    }
+}
+
+impl std::error::Error for LinkdropError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Http(e) | Self::TooManyRedirects(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for LinkdropError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// Why [`crate::Builder::redirect_filter`] stopped a redirect chain, passed
+/// through reqwest as the source of its redirect error.
+#[derive(Debug)]
+pub(crate) struct RefusedRedirect(pub Vec<String>);
+
+impl fmt::Display for RefusedRedirect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("redirect refused")
+    }
+}
+
+impl std::error::Error for RefusedRedirect {}
+
+impl From<reqwest::Error> for LinkdropError {
+    fn from(e: reqwest::Error) -> Self {
+        let refused = std::error::Error::source(&e).and_then(|source| source.downcast_ref::<RefusedRedirect>());
+        if let Some(RefusedRedirect(chain)) = refused {
+            return Self::RedirectRefused { chain: chain.clone() };
+        }
+        if e.is_redirect() {
+            return Self::TooManyRedirects(e);
+        }
+        Self::Http(e)
+    }
+}
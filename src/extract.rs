@@ -0,0 +1,1017 @@
+use std::collections::{HashMap, HashSet};
+
+use once_cell::sync::Lazy;
+use reqwest::Url;
+use scraper::Selector;
+
+use crate::{IconCandidate, LinkMetadata};
+
+/// Most bytes of HTML kept in [`LinkMetadata::raw_html`].
+const RAW_HTML_LIMIT: usize = 64 * 1024;
+
+/// Paragraphs shorter than this are skipped by the first-paragraph description.
+const MIN_PARAGRAPH_CHARS: usize = 80;
+
+/// The first-paragraph description is cut to at most this many characters.
+const MAX_PARAGRAPH_CHARS: usize = 300;
+
+// Compiled on first use and shared by every extraction.
+static TITLE: Lazy<Selector> = Lazy::new(|| Selector::parse("title").unwrap());
+static H1: Lazy<Selector> = Lazy::new(|| Selector::parse("h1").unwrap());
+static META: Lazy<Selector> = Lazy::new(|| Selector::parse("meta").unwrap());
+static CANONICAL: Lazy<Selector> = Lazy::new(|| Selector::parse(r#"link[rel~="canonical"]"#).unwrap());
+static AMP: Lazy<Selector> = Lazy::new(|| Selector::parse("html[amp], html[⚡]").unwrap());
+static OEMBED: Lazy<Selector> = Lazy::new(|| Selector::parse(r#"link[rel~="alternate"][type="application/json+oembed"]"#).unwrap());
+static MANIFEST: Lazy<Selector> = Lazy::new(|| Selector::parse(r#"link[rel~="manifest"]"#).unwrap());
+static ARTICLE_P: Lazy<Selector> = Lazy::new(|| Selector::parse("article p").unwrap());
+static MAIN_P: Lazy<Selector> = Lazy::new(|| Selector::parse("main p").unwrap());
+static BODY_P: Lazy<Selector> = Lazy::new(|| Selector::parse("body p").unwrap());
+static NOSCRIPT: Lazy<Selector> = Lazy::new(|| Selector::parse("noscript").unwrap());
+static JSON_STATE: Lazy<Selector> = Lazy::new(|| Selector::parse(r#"script[type="application/json"]"#).unwrap());
+static PRELOAD_SRCSET: Lazy<Selector> = Lazy::new(|| Selector::parse(r#"link[rel~="preload"][as="image"][imagesrcset]"#).unwrap());
+static IMG_SRCSET: Lazy<Selector> = Lazy::new(|| Selector::parse("img[srcset]").unwrap());
+static OG_LOCALE: Lazy<Selector> = Lazy::new(|| Selector::parse(r#"meta[property="og:locale"], meta[property="og:locale:alternate"]"#).unwrap());
+static OG_TITLE: Lazy<Selector> = Lazy::new(|| Selector::parse(r#"meta[property="og:title"]"#).unwrap());
+static DESCRIPTION: Lazy<Selector> = Lazy::new(|| Selector::parse(r#"meta[name="description"]"#).unwrap());
+static OG_DESCRIPTION: Lazy<Selector> = Lazy::new(|| Selector::parse(r#"meta[property="og:description"]"#).unwrap());
+static ITEMPROP_DESCRIPTION: Lazy<Selector> = Lazy::new(|| Selector::parse(r#"meta[itemprop="description"]"#).unwrap());
+static ICON: Lazy<Selector> = Lazy::new(|| Selector::parse(r#"link[rel~="icon"], link[rel~="mask-icon"]"#).unwrap());
+
+/// How to choose between the page's `description` and `og:description`.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum DescriptionStrategy {
+    /// Use `meta[name="description"]`, falling back to `og:description`.
+    #[default]
+    First,
+    /// Use whichever non-empty candidate is longest.
+    Longest,
+}
+
+/// A set of [`LinkMetadata`] fields to extract; see [`crate::Builder::fields`].
+/// Combine with `|`, e.g. `Fields::TITLE | Fields::FAVICON`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fields(u8);
+
+impl Fields {
+    pub const TITLE: Self = Self(1);
+    pub const DESCRIPTION: Self = Self(1 << 1);
+    /// `image` and `images`.
+    pub const IMAGE: Self = Self(1 << 2);
+    /// `favicon` and `icons`.
+    pub const FAVICON: Self = Self(1 << 3);
+    pub const KEYWORDS: Self = Self(1 << 4);
+    pub const ALL: Self = Self(0b1_1111);
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl Default for Fields {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl std::ops::BitOr for Fields {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// Settings applied when extracting metadata from a page.
+#[derive(Clone, Default)]
+pub(crate) struct ExtractConfig {
+    pub fields: Fields,
+    pub description_strategy: DescriptionStrategy,
+    /// Fall back to the first substantial body paragraph for `description`.
+    pub paragraph_description: bool,
+    /// Look in `<noscript>` and inline JSON state for a missing title.
+    pub spa_title: bool,
+    pub record_sources: bool,
+    pub include_raw_html: bool,
+    /// Language tags from [`crate::Builder::accept_language`], most preferred first.
+    pub languages: Vec<String>,
+    /// From [`crate::Builder::field_selector`], in the order given.
+    pub custom_selectors: Vec<CustomSelector>,
+}
+
+/// A user-supplied last resort for some fields.
+#[derive(Clone)]
+pub(crate) struct CustomSelector {
+    field: Fields,
+    css: String,
+    selector: Selector,
+    /// Read this attribute of the match instead of its text.
+    attribute: Option<String>,
+}
+
+impl CustomSelector {
+    pub fn parse(field: Fields, css: &str, attribute: Option<String>) -> Result<Self, String> {
+        let selector = Selector::parse(css).map_err(|e| format!("invalid field selector `{css}`: {e}"))?;
+        Ok(Self {
+            field,
+            css: css.to_string(),
+            selector,
+            attribute,
+        })
+    }
+}
+
+/// What [`extract`] found besides the metadata itself.
+pub(crate) struct Extracted {
+    pub metadata: LinkMetadata,
+    /// The canonical URL of an AMP page, when it points somewhere else.
+    pub amp_canonical: Option<Url>,
+    /// The JSON oEmbed endpoint the page advertises.
+    pub oembed: Option<Url>,
+    /// The page's web app manifest.
+    pub manifest: Option<Url>,
+}
+
+/// Extract metadata from HTML the caller already has, resolving relative
+/// links against `base_url` (which also becomes the result's `url`). This is
+/// the same extraction the plugin runs on pages it fetches, with default
+/// settings.
+pub fn extract_metadata(html: &str, base_url: &Url) -> LinkMetadata {
+    extract(base_url.as_str(), base_url, html, &ExtractConfig::default()).metadata
+}
+
+pub(crate) fn extract(url: &str, base_url: &Url, html: &str, config: &ExtractConfig) -> Extracted {
+    let document = scraper::Html::parse_document(html);
+
+    let mut sources = HashMap::new();
+    let mut record = |field: &str, source: &str| {
+        if config.record_sources {
+            sources.insert(field.to_string(), source.to_string());
+        }
+    };
+
+    // Title
+    let wants_title = config.fields.contains(Fields::TITLE);
+    let mut title = None;
+    if wants_title {
+        title = document
+            .select(&TITLE)
+            .filter(|elem| {
+                // An inline SVG's <title> is a tooltip, not the page title.
+                !elem
+                    .ancestors()
+                    .any(|a| a.value().as_element().is_some_and(|el| el.name() == "svg"))
+            })
+            .map(|elem| elem.text().collect::<String>().trim().to_string())
+            .find(|text| !text.is_empty());
+    }
+    if title.is_some() {
+        record("title", "<title>");
+    }
+    if title.is_none() && wants_title {
+        if let Some(meta) = document.select(&OG_TITLE).next() {
+            if let Some(content) = meta.value().attr("content") {
+                title = Some(content.to_string());
+                record("title", "og:title");
+            }
+        }
+    }
+    if title.is_none() && wants_title {
+        title = itemprop(&document, "name");
+        if title.is_some() {
+            record("title", r#"<meta itemprop="name">"#);
+        }
+    }
+
+    // Description
+    let wants_description = config.fields.contains(Fields::DESCRIPTION);
+    let mut candidates = [
+        (&DESCRIPTION, r#"<meta name="description">"#),
+        (&OG_DESCRIPTION, "og:description"),
+        (&ITEMPROP_DESCRIPTION, r#"<meta itemprop="description">"#),
+    ]
+    .into_iter()
+    .filter(|_| wants_description)
+    .filter_map(|(selector, source)| {
+        let meta = document.select(selector).next()?;
+        meta.value().attr("content").map(|d| (source, d.to_string()))
+    });
+    let description = match config.description_strategy {
+        DescriptionStrategy::First => candidates.next(),
+        DescriptionStrategy::Longest => candidates
+            .filter(|(_, d)| !d.trim().is_empty())
+            .reduce(|best, c| if c.1.chars().count() > best.1.chars().count() { c } else { best }),
+    };
+    let mut description = description.map(|(source, d)| {
+        record("description", source);
+        d
+    });
+
+    // Localized alternates
+    if !config.languages.is_empty() {
+        let localized_title = wants_title.then(|| localized_meta(&document, "og:title", &config.languages));
+        if let Some(localized) = localized_title.flatten() {
+            title = Some(localized);
+            record("title", "og:title");
+        }
+        let localized_description =
+            wants_description.then(|| localized_meta(&document, "og:description", &config.languages));
+        if let Some(localized) = localized_description.flatten() {
+            description = Some(localized);
+            record("description", "og:description");
+        }
+    }
+
+    // Client-rendered title
+    if title.is_none() && wants_title && config.spa_title {
+        if let Some(found) = noscript_title(&document) {
+            title = Some(found);
+            record("title", "<noscript>");
+        } else if let Some(found) = state_title(&document) {
+            title = Some(found);
+            record("title", r#"<script type="application/json">"#);
+        }
+    }
+
+    // First paragraph
+    if description.is_none() && wants_description && config.paragraph_description {
+        description = first_paragraph(&document);
+        if description.is_some() {
+            record("description", "<p>");
+        }
+    }
+
+    // Custom title and description
+    if title.is_none() && wants_title {
+        if let Some((source, found)) = custom_value(&document, config, Fields::TITLE) {
+            title = Some(found);
+            record("title", source);
+        }
+    }
+    if description.is_none() && wants_description {
+        if let Some((source, found)) = custom_value(&document, config, Fields::DESCRIPTION) {
+            description = Some(found);
+            record("description", source);
+        }
+    }
+
+    // Image
+    let mut images: Vec<String> = Vec::new();
+    let mut image = None;
+    if config.fields.contains(Fields::IMAGE) {
+        let candidates = image_candidates(&document);
+        // The largest declared image wins; without dimensions, og:image beats
+        // twitter:image and earlier beats later.
+        let best = candidates
+            .iter()
+            .filter(|c| c.source == "og:image")
+            .chain(candidates.iter().filter(|c| c.source != "og:image"))
+            .reduce(|best, c| if c.area() > best.area() { c } else { best });
+        if let Some(best) = best {
+            image = Some(best.url.clone());
+            record("image", best.source);
+        } else if let Some(microdata) = itemprop(&document, "image") {
+            image = Some(normalize_url(resolve_href(base_url, &microdata)));
+            record("image", r#"<meta itemprop="image">"#);
+        } else if let Some((source, srcset)) = page_srcset(&document) {
+            image = largest_in_srcset(srcset).map(|src| normalize_url(resolve_href(base_url, src)));
+            if image.is_some() {
+                record("image", source);
+            }
+        }
+        if image.is_none() {
+            if let Some((source, found)) = custom_value(&document, config, Fields::IMAGE) {
+                image = Some(normalize_url(resolve_href(base_url, &found)));
+                record("image", source);
+            }
+        }
+        images = candidates.into_iter().map(|c| c.url).collect();
+        images.extend(image.clone());
+    }
+    let images = dedupe_by(images, |i| i);
+
+    // Favicon
+    let mut icons: Vec<IconCandidate> = Vec::new();
+    if config.fields.contains(Fields::FAVICON) {
+        icons = document
+            .select(&ICON)
+            .filter_map(|l| {
+                let attr = |name| l.value().attr(name).map(str::to_string);
+                let mask = l.value().attr("rel").is_some_and(is_mask_icon);
+                Some(IconCandidate {
+                    url: normalize_url(resolve_href(base_url, l.value().attr("href")?)),
+                    rel: attr("rel").unwrap_or_else(|| "icon".to_string()),
+                    sizes: attr("sizes"),
+                    // Safari pinned-tab icons are always SVG.
+                    mime_type: attr("type").or_else(|| mask.then(|| "image/svg+xml".to_string())),
+                    dimensions: None,
+                })
+            })
+            .collect();
+        // A single-colour mask icon is only the favicon when there is nothing else.
+        icons.sort_by_key(|icon| is_mask_icon(&icon.rel));
+    }
+    let mut favicon_source = None;
+    if icons.is_empty() && config.fields.contains(Fields::FAVICON) {
+        if let Some((source, found)) = custom_value(&document, config, Fields::FAVICON) {
+            icons.push(IconCandidate {
+                url: normalize_url(resolve_href(base_url, &found)),
+                rel: "icon".to_string(),
+                sizes: None,
+                mime_type: None,
+                dimensions: None,
+            });
+            favicon_source = Some(source);
+        }
+    }
+    let icons = dedupe_by(icons, |i| &i.url);
+    let favicon = icons.first().map(|i| {
+        match favicon_source {
+            Some(source) => record("favicon", source),
+            None => record("favicon", &format!(r#"<link rel="{}">"#, i.rel)),
+        }
+        i.url.clone()
+    });
+
+    // Keywords
+    let mut keywords = Vec::new();
+    if config.fields.contains(Fields::KEYWORDS) {
+        for meta in document.select(&META) {
+            let meta = meta.value();
+            let Some(content) = meta.attr("content") else { continue };
+            if meta.attr("name").is_some_and(|name| name.eq_ignore_ascii_case("keywords")) {
+                keywords.extend(content.split(',').map(str::trim).filter(|k| !k.is_empty()).map(str::to_string));
+            } else if meta.attr("property") == Some("article:tag") && !content.trim().is_empty() {
+                keywords.push(content.trim().to_string());
+            }
+        }
+        if let Some((_, found)) = keywords.is_empty().then(|| custom_value(&document, config, Fields::KEYWORDS)).flatten() {
+            keywords.extend(found.split(',').map(str::trim).filter(|k| !k.is_empty()).map(str::to_string));
+        }
+    }
+    let keywords = dedupe_by(keywords, |k| k);
+
+    // Canonical
+    let canonical_url = document
+        .select(&CANONICAL)
+        .find_map(|l| l.value().attr("href"))
+        .and_then(|href| base_url.join(href.trim()).ok());
+    let is_amp = document
+        .select(&AMP)
+        .next()
+        .is_some();
+    let amp_canonical = canonical_url
+        .clone()
+        .filter(|canonical| is_amp && canonical.as_str() != base_url.as_str());
+
+    // oEmbed discovery
+    let oembed = document
+        .select(&OEMBED)
+        .find_map(|l| l.value().attr("href"))
+        .and_then(|href| base_url.join(href.trim()).ok());
+    let manifest = document
+        .select(&MANIFEST)
+        .find_map(|l| l.value().attr("href"))
+        .and_then(|href| base_url.join(href.trim()).ok());
+
+    Extracted {
+        metadata: LinkMetadata {
+            url: url.to_string(),
+            title,
+            description,
+            image,
+            favicon,
+            icons,
+            images,
+            keywords,
+            canonical_url: canonical_url.map(String::from),
+            sources,
+            raw_html: config.include_raw_html.then(|| truncate(html, RAW_HTML_LIMIT).to_string()),
+            ..Default::default()
+        },
+        amp_canonical,
+        oembed,
+        manifest,
+    }
+}
+
+/// The first paragraph of at least [`MIN_PARAGRAPH_CHARS`] inside `<article>`,
+/// else `<main>`, else anywhere in the body, with whitespace collapsed, script
+/// and style text dropped, and cut at a word to [`MAX_PARAGRAPH_CHARS`].
+fn first_paragraph(document: &scraper::Html) -> Option<String> {
+    [&ARTICLE_P, &MAIN_P, &BODY_P].into_iter().find_map(|selector| {
+        document
+            .select(selector)
+            .map(|p| {
+                let text: String = p
+                    .descendants()
+                    .filter(|node| {
+                        !node
+                            .ancestors()
+                            .any(|a| a.value().as_element().is_some_and(|el| matches!(el.name(), "script" | "style")))
+                    })
+                    .filter_map(|node| node.value().as_text().map(|t| &**t))
+                    .collect();
+                text.split_whitespace().collect::<Vec<_>>().join(" ")
+            })
+            .find(|text| text.chars().count() >= MIN_PARAGRAPH_CHARS)
+            .map(|text| {
+                if text.chars().count() <= MAX_PARAGRAPH_CHARS {
+                    return text;
+                }
+                let cut: String = text.chars().take(MAX_PARAGRAPH_CHARS).collect();
+                let cut = cut.rsplit_once(' ').map_or(cut.as_str(), |(head, _)| head);
+                format!("{}…", cut.trim_end_matches(|c: char| c.is_ascii_punctuation()))
+            })
+    })
+}
+
+/// The `<title>`, else first `<h1>`, inside the page's `<noscript>` blocks.
+/// Those hold raw markup when parsed, so each is parsed again on its own.
+fn noscript_title(document: &scraper::Html) -> Option<String> {
+    let fallbacks: Vec<_> = document
+        .select(&NOSCRIPT)
+        .map(|noscript| scraper::Html::parse_fragment(&noscript.text().collect::<String>()))
+        .collect();
+    [&TITLE, &H1].into_iter().find_map(|selector| {
+        fallbacks.iter().find_map(|fragment| {
+            fragment
+                .select(selector)
+                .map(|elem| elem.text().collect::<Vec<_>>().join(" "))
+                .map(|text| text.split_whitespace().collect::<Vec<_>>().join(" "))
+                .find(|text| !text.is_empty())
+        })
+    })
+}
+
+/// A `"title"` string from the JSON state blobs SPAs embed, such as Next.js's
+/// `<script id="__NEXT_DATA__" type="application/json">`. Only the first few
+/// levels of each blob are searched, shallowest first, to avoid picking up the
+/// title of some unrelated nested item.
+fn state_title(document: &scraper::Html) -> Option<String> {
+    const MAX_DEPTH: usize = 4;
+    document
+        .select(&JSON_STATE)
+        .filter_map(|script| serde_json::from_str::<serde_json::Value>(&script.text().collect::<String>()).ok())
+        .find_map(|state| {
+            let mut level = vec![&state];
+            for _ in 0..MAX_DEPTH {
+                let objects = level.iter().filter_map(|value| value.as_object());
+                let title = objects
+                    .clone()
+                    .filter_map(|object| object.get("title")?.as_str())
+                    .map(str::trim)
+                    .find(|title| !title.is_empty());
+                if let Some(title) = title {
+                    return Some(title.to_string());
+                }
+                level = objects.flat_map(|object| object.values()).collect();
+            }
+            None
+        })
+}
+
+/// A preview image declared by an `og:image` or `twitter:image` tag.
+struct ImageCandidate {
+    url: String,
+    source: &'static str,
+    /// From the `og:image:width`/`og:image:height` tags that follow an `og:image`.
+    width: Option<u64>,
+    height: Option<u64>,
+}
+
+impl ImageCandidate {
+    fn area(&self) -> u64 {
+        self.width.unwrap_or(0) * self.height.unwrap_or(0)
+    }
+}
+
+/// Every `og:image` and `twitter:image` in document order.
+fn image_candidates(document: &scraper::Html) -> Vec<ImageCandidate> {
+    let mut candidates: Vec<ImageCandidate> = Vec::new();
+    for meta in document.select(&META) {
+        let meta = meta.value();
+        let Some(content) = meta.attr("content").map(str::trim).filter(|c| !c.is_empty()) else {
+            continue;
+        };
+        let candidate = |source| ImageCandidate {
+            url: normalize_url(content.to_string()),
+            source,
+            width: None,
+            height: None,
+        };
+        let last_og = candidates.iter_mut().rev().find(|c| c.source == "og:image");
+        match meta.attr("property").or(meta.attr("name")) {
+            Some("og:image") => candidates.push(candidate("og:image")),
+            Some("twitter:image" | "twitter:image:src") => candidates.push(candidate("twitter:image")),
+            Some("og:image:width") => {
+                if let Some(last) = last_og {
+                    last.width = content.parse().ok();
+                }
+            }
+            Some("og:image:height") => {
+                if let Some(last) = last_og {
+                    last.height = content.parse().ok();
+                }
+            }
+            _ => {}
+        }
+    }
+    candidates
+}
+
+/// The first responsive image hint in the page: a preloaded
+/// `<link rel="preload" as="image" imagesrcset>`, else an `<img srcset>`.
+fn page_srcset(document: &scraper::Html) -> Option<(&'static str, &str)> {
+    [
+        (&PRELOAD_SRCSET, "imagesrcset", r#"<link rel="preload" imagesrcset>"#),
+        (&IMG_SRCSET, "srcset", "<img srcset>"),
+    ]
+    .into_iter()
+    .find_map(|(selector, attr, source)| {
+        let srcset = document
+            .select(selector)
+            .find_map(|elem| elem.value().attr(attr).filter(|s| !s.trim().is_empty()))?;
+        Some((source, srcset))
+    })
+}
+
+/// The URL of the `srcset` candidate with the largest width (`640w`) or pixel
+/// density (`2x`) descriptor. Candidates without one count as `1x`.
+fn largest_in_srcset(srcset: &str) -> Option<&str> {
+    srcset
+        .split(',')
+        .filter_map(|candidate| {
+            let mut parts = candidate.split_whitespace();
+            let url = parts.next()?;
+            let descriptor = parts.next().unwrap_or("1x");
+            let size = descriptor
+                .strip_suffix('w')
+                .or_else(|| descriptor.strip_suffix('x'))
+                .and_then(|n| n.parse::<f64>().ok())
+                .unwrap_or(1.0);
+            Some((url, size))
+        })
+        .reduce(|best, c| if c.1 > best.1 { c } else { best })
+        .map(|(url, _)| url)
+}
+
+/// Whether a `rel` value marks a Safari pinned-tab `mask-icon`.
+fn is_mask_icon(rel: &str) -> bool {
+    rel.split_whitespace().any(|r| r.eq_ignore_ascii_case("mask-icon"))
+}
+
+/// The first non-empty value found by the custom selectors for `field`: the
+/// chosen attribute of a match, or its text with whitespace collapsed. Comes
+/// with the selector, as its source.
+fn custom_value<'a>(document: &scraper::Html, config: &'a ExtractConfig, field: Fields) -> Option<(&'a str, String)> {
+    config.custom_selectors.iter().filter(|c| c.field.contains(field)).find_map(|custom| {
+        document.select(&custom.selector).find_map(|elem| {
+            let value = match &custom.attribute {
+                Some(attribute) => elem.value().attr(attribute)?.trim().to_string(),
+                None => elem.text().collect::<Vec<_>>().join(" ").split_whitespace().collect::<Vec<_>>().join(" "),
+            };
+            (!value.is_empty()).then_some((custom.css.as_str(), value))
+        })
+    })
+}
+
+/// The first non-empty `<meta itemprop=<name>>` value, for pages that only
+/// describe themselves with schema.org microdata.
+fn itemprop(document: &scraper::Html, name: &str) -> Option<String> {
+    document
+        .select(&META)
+        .filter(|m| m.value().attr("itemprop") == Some(name))
+        .filter_map(|m| m.value().attr("content"))
+        .map(str::trim)
+        .find(|content| !content.is_empty())
+        .map(str::to_string)
+}
+
+/// The longest prefix of `s` that is at most `max` bytes and ends on a char boundary.
+fn truncate(s: &str, max: usize) -> &str {
+    let mut end = s.len().min(max);
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+/// Language tags of an `Accept-Language` value, highest `q` first.
+pub(crate) fn parse_accept_language(value: &str) -> Vec<String> {
+    let mut tags: Vec<(String, f32)> = value
+        .split(',')
+        .filter_map(|part| {
+            let mut pieces = part.split(';');
+            let tag = pieces.next()?.trim();
+            let q = pieces
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse().ok())?;
+            (!tag.is_empty() && tag != "*" && q > 0.0).then(|| (tag.to_string(), q))
+        })
+        .collect();
+    tags.sort_by(|a, b| b.1.total_cmp(&a.1));
+    tags.into_iter().map(|(tag, _)| tag).collect()
+}
+
+/// The `meta[property=<property>]` value whose locale best matches
+/// `languages`; see [`crate::Builder::accept_language`] for the rules. `None`
+/// unless the page offers at least two localized values to choose from.
+fn localized_meta(document: &scraper::Html, property: &str, languages: &[String]) -> Option<String> {
+    let locales: Vec<String> = document
+        .select(&OG_LOCALE)
+        .filter_map(|m| m.value().attr("content"))
+        .map(|l| l.trim().to_string())
+        .collect();
+    let metas: Vec<_> = document
+        .select(&META)
+        .filter(|m| m.value().attr("property") == Some(property))
+        .collect();
+    let positional = metas.len() == locales.len();
+    let candidates: Vec<(String, String)> = metas
+        .iter()
+        .enumerate()
+        .filter_map(|(i, meta)| {
+            let content = meta.value().attr("content")?.trim();
+            let locale = match meta.value().attr("lang") {
+                Some(lang) => lang.to_string(),
+                None if positional => locales[i].clone(),
+                None => return None,
+            };
+            Some((locale.replace('_', "-").to_ascii_lowercase(), content.to_string()))
+        })
+        .collect();
+    if candidates.len() < 2 {
+        return None;
+    }
+
+    let primary = |tag: &str| tag.split('-').next().unwrap_or_default().to_string();
+    languages.iter().find_map(|language| {
+        let language = language.replace('_', "-").to_ascii_lowercase();
+        candidates
+            .iter()
+            .find(|(locale, _)| *locale == language)
+            .or_else(|| candidates.iter().find(|(locale, _)| primary(locale) == primary(&language)))
+            .map(|(_, content)| content.clone())
+    })
+}
+
+/// Drop the fragment and sort the query of an http(s) URL so equivalent URLs
+/// compare equal. Anything else, including `data:` URLs, is left untouched.
+pub(crate) fn normalize_url(raw: String) -> String {
+    let Ok(mut url) = Url::parse(&raw) else { return raw };
+    if !matches!(url.scheme(), "http" | "https") {
+        return raw;
+    }
+    url.set_fragment(None);
+    let mut pairs: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+    if pairs.is_empty() {
+        url.set_query(None);
+    } else {
+        pairs.sort();
+        url.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    url.to_string()
+}
+
+/// Remove later items whose key repeats an earlier one, keeping document order.
+fn dedupe_by<T>(items: Vec<T>, key: impl Fn(&T) -> &String) -> Vec<T> {
+    let mut seen = HashSet::new();
+    items.into_iter().filter(|item| seen.insert(key(item).clone())).collect()
+}
+
+/// Resolve an `href`/`content` URL from the page against `base_url`. Inline
+/// `data:` URLs are returned as-is. Everything else, absolute URLs included, goes
+/// through the URL parser so spaces and other unescaped characters come out
+/// percent-encoded; the raw value is kept only if it can't be parsed at all.
+fn resolve_href(base_url: &Url, href: &str) -> String {
+    let href = href.trim();
+    let is_data = href.get(..5).is_some_and(|scheme| scheme.eq_ignore_ascii_case("data:"));
+    if is_data {
+        return href.to_string();
+    }
+    base_url.join(href).map(|u| u.to_string()).unwrap_or_else(|_| href.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base() -> Url {
+        Url::parse("https://example.com/articles/one").unwrap()
+    }
+
+    #[test]
+    fn data_urls_pass_through_untouched() {
+        let png = "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNk+M9QDwADhgGAWjR9awAAAABJRU5ErkJggg==";
+        let html = format!(
+            r#"<head><link rel="icon" href="{png}"><meta property="og:image" content="{png}"></head>"#
+        );
+        let meta = extract_metadata(&html, &base());
+        assert_eq!(meta.favicon.as_deref(), Some(png));
+        assert_eq!(meta.image.as_deref(), Some(png));
+    }
+
+    #[test]
+    fn icons_and_images_differing_by_fragment_are_deduped() {
+        let html = r#"<head>
+            <link rel="icon" href="/favicon.png#light">
+            <link rel="apple-touch-icon icon" href="/touch.png">
+            <link rel="icon" href="/favicon.png#dark">
+            <meta property="og:image" content="https://example.com/hero.jpg?b=2&a=1#top">
+            <meta name="twitter:image" content="https://example.com/hero.jpg?a=1&b=2">
+        </head>"#;
+        let meta = extract_metadata(html, &base());
+        let icons: Vec<_> = meta.icons.iter().map(|i| i.url.as_str()).collect();
+        assert_eq!(icons, ["https://example.com/favicon.png", "https://example.com/touch.png"]);
+        assert_eq!(meta.images, ["https://example.com/hero.jpg?a=1&b=2"]);
+    }
+
+    #[test]
+    fn richer_preview_image_wins() {
+        let config = ExtractConfig {
+            record_sources: true,
+            ..Default::default()
+        };
+        let html = r#"<head>
+            <meta name="twitter:image" content="https://example.com/card.png">
+            <meta property="og:image" content="https://example.com/og.png">
+        </head>"#;
+        let meta = extract("u", &base(), html, &config).metadata;
+        assert_eq!(meta.image.as_deref(), Some("https://example.com/og.png"));
+        assert_eq!(meta.sources.get("image").map(String::as_str), Some("og:image"));
+
+        let html = r#"<head>
+            <meta property="og:image" content="https://example.com/small.png">
+            <meta property="og:image:width" content="200">
+            <meta property="og:image:height" content="100">
+            <meta property="og:image" content="https://example.com/large.png">
+            <meta property="og:image:width" content="1200">
+            <meta property="og:image:height" content="630">
+            <meta name="twitter:image" content="https://example.com/card.png">
+        </head>"#;
+        let meta = extract("u", &base(), html, &config).metadata;
+        assert_eq!(meta.image.as_deref(), Some("https://example.com/large.png"));
+    }
+
+    #[test]
+    fn spa_title_reads_noscript_then_json_state() {
+        let config = ExtractConfig {
+            spa_title: true,
+            ..Default::default()
+        };
+        let noscript = r#"<body><div id="root"></div>
+            <noscript><h1>  Enable   JavaScript </h1></noscript>
+            <noscript><title>My App</title></noscript>
+        </body>"#;
+        assert_eq!(extract("u", &base(), noscript, &config).metadata.title.as_deref(), Some("My App"));
+        assert_eq!(extract_metadata(noscript, &base()).title, None);
+
+        let state = r#"<body><div id="__next"></div>
+            <script id="__NEXT_DATA__" type="application/json">
+                {"props": {"pageProps": {"title": "From state", "items": [{"title": "Nested"}]}}}
+            </script>
+        </body>"#;
+        assert_eq!(extract("u", &base(), state, &config).metadata.title.as_deref(), Some("From state"));
+    }
+
+    #[test]
+    fn srcset_fallback_picks_the_largest_candidate() {
+        let html = r#"<body>
+            <img src="/hero-small.jpg" srcset="/hero-480.jpg 480w, /hero-1600.jpg 1600w, /hero-800.jpg 800w">
+        </body>"#;
+        let meta = extract_metadata(html, &base());
+        assert_eq!(meta.image.as_deref(), Some("https://example.com/hero-1600.jpg"));
+
+        let html = r#"<head>
+            <link rel="preload" as="image" imagesrcset="cover.png, cover@3x.png 3x, cover@2x.png 2x">
+        </head><body><img srcset="/other.jpg 2000w"></body>"#;
+        let meta = extract_metadata(html, &base());
+        assert_eq!(meta.image.as_deref(), Some("https://example.com/articles/cover@3x.png"));
+    }
+
+    #[test]
+    fn longest_strategy_picks_the_richer_description() {
+        let html = r#"<head>
+            <meta name="description" content="Short snippet…">
+            <meta property="og:description" content="The full summary of the article, much longer than the snippet.">
+        </head>"#;
+        let first = extract("u", &base(), html, &ExtractConfig::default()).metadata;
+        assert_eq!(first.description.as_deref(), Some("Short snippet…"));
+        let config = ExtractConfig {
+            description_strategy: DescriptionStrategy::Longest,
+            ..Default::default()
+        };
+        let longest = extract("u", &base(), html, &config).metadata;
+        assert_eq!(
+            longest.description.as_deref(),
+            Some("The full summary of the article, much longer than the snippet.")
+        );
+    }
+
+    #[test]
+    fn sources_name_the_tag_behind_each_field() {
+        let html = r#"<head>
+            <meta property="og:title" content="From OG">
+            <meta property="og:description" content="Described">
+            <meta name="twitter:image" content="https://example.com/card.png">
+            <link rel="shortcut icon" href="/favicon.ico">
+        </head>"#;
+        let config = ExtractConfig {
+            record_sources: true,
+            ..Default::default()
+        };
+        let meta = extract("u", &base(), html, &config).metadata;
+        let source = |field: &str| meta.sources.get(field).map(String::as_str);
+        assert_eq!(source("title"), Some("og:title"));
+        assert_eq!(source("description"), Some("og:description"));
+        assert_eq!(source("image"), Some("twitter:image"));
+        assert_eq!(source("favicon"), Some(r#"<link rel="shortcut icon">"#));
+
+        assert!(extract_metadata(html, &base()).sources.is_empty());
+    }
+
+    #[test]
+    fn preferred_language_picks_the_matching_alternate() {
+        let html = r#"<head>
+            <meta property="og:locale" content="en_US">
+            <meta property="og:locale:alternate" content="fr_FR">
+            <meta property="og:title" content="Hello">
+            <meta property="og:title" content="Bonjour">
+            <meta property="og:description" content="In English">
+            <meta property="og:description" content="En français">
+        </head>"#;
+        let config = |accept_language| ExtractConfig {
+            languages: parse_accept_language(accept_language),
+            ..Default::default()
+        };
+        let french = extract("u", &base(), html, &config("fr-CA, fr;q=0.9, en;q=0.5")).metadata;
+        assert_eq!(french.title.as_deref(), Some("Bonjour"));
+        assert_eq!(french.description.as_deref(), Some("En français"));
+        let german = extract("u", &base(), html, &config("de")).metadata;
+        assert_eq!(german.title.as_deref(), Some("Hello"));
+        assert_eq!(german.description.as_deref(), Some("In English"));
+    }
+
+    #[test]
+    fn single_locale_page_keeps_the_usual_title() {
+        let html = r#"<head>
+            <title>Accueil</title>
+            <meta property="og:locale" content="fr_FR">
+            <meta property="og:title" content="Bienvenue sur le site">
+        </head>"#;
+        let config = ExtractConfig { languages: parse_accept_language("fr"), ..Default::default() };
+        let meta = extract("u", &base(), html, &config).metadata;
+        assert_eq!(meta.title.as_deref(), Some("Accueil"));
+    }
+
+    #[test]
+    fn first_non_empty_title_outside_svg_wins() {
+        let html = r#"<html><head><title>  </title></head><body>
+            <svg><title>Chart tooltip</title></svg>
+            <title>Real title</title>
+        </body></html>"#;
+        assert_eq!(extract_metadata(html, &base()).title.as_deref(), Some("Real title"));
+    }
+
+    #[test]
+    fn extract_metadata_reads_a_fixture_without_fetching() {
+        let html = r#"<!doctype html><html><head>
+            <title>Fixture page</title>
+            <meta name="description" content="A page on disk.">
+            <meta property="og:image" content="https://cdn.example.com/cover.jpg">
+            <link rel="icon" href="../favicon.png">
+            <meta name="keywords" content="rust, tauri">
+        </head><body><p>Body</p></body></html>"#;
+        let meta = extract_metadata(html, &base());
+        assert_eq!(meta.url, "https://example.com/articles/one");
+        assert_eq!(meta.title.as_deref(), Some("Fixture page"));
+        assert_eq!(meta.description.as_deref(), Some("A page on disk."));
+        assert_eq!(meta.image.as_deref(), Some("https://cdn.example.com/cover.jpg"));
+        assert_eq!(meta.favicon.as_deref(), Some("https://example.com/favicon.png"));
+        assert_eq!(meta.keywords, ["rust", "tauri"]);
+        assert_eq!(meta.final_url, None);
+    }
+
+    #[test]
+    fn keywords_merge_meta_keywords_and_article_tags() {
+        let html = r#"<head>
+            <meta name="Keywords" content=" rust, tauri, , rust ">
+            <meta property="article:tag" content="tauri">
+            <meta property="article:tag" content=" Desktop apps ">
+        </head>"#;
+        assert_eq!(extract_metadata(html, &base()).keywords, ["rust", "tauri", "Desktop apps"]);
+        assert!(extract_metadata("<title>No tags</title>", &base()).keywords.is_empty());
+    }
+
+    #[test]
+    fn open_graph_tags_and_favicon_are_extracted() {
+        let html = r#"<head>
+            <meta property="og:title" content="OG title">
+            <meta property="og:description" content="OG description">
+            <meta property="og:image" content="https://cdn.example.com/og.png">
+            <link rel="icon" type="image/png" href="/favicon-32.png">
+        </head>"#;
+        let meta = extract_metadata(html, &base());
+        assert_eq!(meta.title.as_deref(), Some("OG title"));
+        assert_eq!(meta.description.as_deref(), Some("OG description"));
+        assert_eq!(meta.image.as_deref(), Some("https://cdn.example.com/og.png"));
+        assert_eq!(meta.favicon.as_deref(), Some("https://example.com/favicon-32.png"));
+    }
+
+    #[test]
+    fn shared_selectors_extract_each_document_independently() {
+        let first = r#"<head><title>First</title><meta name="description" content="One"><link rel="icon" href="/1.png"></head>"#;
+        let second = r#"<head><meta property="og:title" content="Second"><link rel="icon" href="/2.png"></head>"#;
+        for _ in 0..2 {
+            let meta = extract_metadata(first, &base());
+            assert_eq!(meta.title.as_deref(), Some("First"));
+            assert_eq!(meta.description.as_deref(), Some("One"));
+            assert_eq!(meta.favicon.as_deref(), Some("https://example.com/1.png"));
+            let meta = extract_metadata(second, &base());
+            assert_eq!(meta.title.as_deref(), Some("Second"));
+            assert_eq!(meta.description, None);
+            assert_eq!(meta.favicon.as_deref(), Some("https://example.com/2.png"));
+        }
+    }
+
+    #[test]
+    fn sole_mask_icon_becomes_the_favicon() {
+        let html = r##"<head><link rel="mask-icon" href="../pinned.svg" color="#000"></head>"##;
+        let meta = extract_metadata(html, &base());
+        assert_eq!(meta.favicon.as_deref(), Some("https://example.com/pinned.svg"));
+        assert_eq!(meta.icons[0].mime_type.as_deref(), Some("image/svg+xml"));
+
+        let html = r##"<head>
+            <link rel="mask-icon" href="/pinned.svg" color="#000">
+            <link rel="icon" href="/favicon.png">
+        </head>"##;
+        let meta = extract_metadata(html, &base());
+        assert_eq!(meta.favicon.as_deref(), Some("https://example.com/favicon.png"));
+        let icons: Vec<_> = meta.icons.iter().map(|i| i.url.as_str()).collect();
+        assert_eq!(icons, ["https://example.com/favicon.png", "https://example.com/pinned.svg"]);
+    }
+
+    #[test]
+    fn favicon_href_with_spaces_is_percent_encoded() {
+        let html = r#"<link rel="icon" href=" /static/my icon (1).png ">"#;
+        let meta = extract_metadata(html, &base());
+        assert_eq!(meta.favicon.as_deref(), Some("https://example.com/static/my%20icon%20(1).png"));
+        assert!(Url::parse(meta.favicon.as_deref().unwrap()).is_ok());
+    }
+
+    #[test]
+    fn unselected_fields_stay_empty() {
+        let html = r#"<head>
+            <title>Kept</title>
+            <meta name="description" content="Dropped">
+            <meta property="og:image" content="https://example.com/a.png">
+            <link rel="icon" href="/favicon.png">
+            <meta name="keywords" content="a, b">
+        </head>"#;
+        let config = ExtractConfig {
+            fields: Fields::TITLE,
+            record_sources: true,
+            ..Default::default()
+        };
+        let meta = extract("u", &base(), html, &config).metadata;
+        assert_eq!(meta.title.as_deref(), Some("Kept"));
+        assert_eq!((meta.description, meta.image, meta.favicon), (None, None, None));
+        assert!(meta.images.is_empty() && meta.icons.is_empty() && meta.keywords.is_empty());
+        assert_eq!(meta.sources.keys().collect::<Vec<_>>(), ["title"]);
+    }
+
+    #[test]
+    fn microdata_fills_in_without_og_or_twitter_tags() {
+        let html = r#"<html itemscope itemtype="https://schema.org/Article"><head>
+            <meta itemprop="name" content="Microdata title">
+            <meta itemprop="description" content="Described with itemprop.">
+            <meta itemprop="image" content="/images/lead.jpg">
+        </head></html>"#;
+        let meta = extract_metadata(html, &base());
+        assert_eq!(meta.title.as_deref(), Some("Microdata title"));
+        assert_eq!(meta.description.as_deref(), Some("Described with itemprop."));
+        assert_eq!(meta.image.as_deref(), Some("https://example.com/images/lead.jpg"));
+    }
+
+    #[test]
+    fn first_substantial_paragraph_becomes_the_description() {
+        let html = r#"<html><head><title>Post</title></head><body>
+            <nav><p>Home</p></nav>
+            <article>
+                <p>Short.</p>
+                <p>The opening paragraph of this article is long enough to stand in for a
+                   description <script>track()</script>and is what the card should show.</p>
+            </article>
+        </body></html>"#;
+        let config = ExtractConfig {
+            paragraph_description: true,
+            ..Default::default()
+        };
+        let meta = extract("u", &base(), html, &config).metadata;
+        assert_eq!(
+            meta.description.as_deref(),
+            Some("The opening paragraph of this article is long enough to stand in for a description and is what the card should show.")
+        );
+        assert_eq!(extract_metadata(html, &base()).description, None);
+    }
+}
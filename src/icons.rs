@@ -0,0 +1,92 @@
+use std::io::Cursor;
+
+use futures::{stream, StreamExt};
+use image::ImageReader;
+use reqwest::header::RANGE;
+use reqwest::{Client, Url};
+
+use crate::{audit, execute, is_public_url, FetchConfig, IconCandidate, IconDimensions};
+
+/// How much of each icon is downloaded to find its dimensions. Enough for the
+/// header of every supported format and for most `.ico` files whole.
+const PROBE_SIZE: u64 = 64 * 1024;
+
+/// How many icons of one page are probed at once.
+const PROBE_CONCURRENCY: usize = 4;
+
+/// Fill in [`IconCandidate::dimensions`] for every icon whose first bytes are
+/// a format the `image` crate recognizes. Icons that fail to download or
+/// decode, such as SVGs, are left without. Icons pointing at a local address
+/// are only probed on the page's own host.
+pub(crate) async fn probe_icons(client: &Client, base_url: &Url, icons: &mut [IconCandidate], config: &FetchConfig) {
+    stream::iter(icons.iter_mut())
+        .for_each_concurrent(PROBE_CONCURRENCY, |icon| async move {
+            icon.dimensions = probe(client, base_url, &icon.url, config).await;
+        })
+        .await;
+}
+
+async fn probe(client: &Client, base_url: &Url, url: &str, config: &FetchConfig) -> Option<IconDimensions> {
+    let url = Url::parse(url).ok()?;
+    if !is_public_url(&url) && url.host_str() != base_url.host_str() {
+        return None;
+    }
+    let req = client.get(url).header(RANGE, format!("bytes=0-{}", PROBE_SIZE - 1));
+    let mut resp = execute(req.timeout(config.favicon_check_timeout), config).await.ok()?;
+    if !resp.status().is_success() {
+        audit(&resp, 0, config);
+        return None;
+    }
+    // Servers that ignore `Range` send the whole icon; keep only the start.
+    let mut bytes = Vec::new();
+    while let Ok(Some(chunk)) = resp.chunk().await {
+        let room = PROBE_SIZE as usize - bytes.len();
+        bytes.extend_from_slice(&chunk[..chunk.len().min(room)]);
+        if bytes.len() as u64 >= PROBE_SIZE {
+            break;
+        }
+    }
+    audit(&resp, bytes.len() as u64, config);
+    let reader = ImageReader::new(Cursor::new(bytes)).with_guessed_format().ok()?;
+    let format = reader.format()?.extensions_str().first()?.to_string();
+    let (width, height) = reader.into_dimensions().ok()?;
+    Some(IconDimensions { width, height, format })
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{ImageFormat, RgbaImage};
+    use wiremock::matchers::path;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+    use crate::tests::{linkdrop, page};
+    use crate::{resolve_link, Builder};
+
+    #[tokio::test]
+    async fn png_icon_reports_its_real_size() {
+        let mut png = Cursor::new(Vec::new());
+        RgbaImage::new(48, 32).write_to(&mut png, ImageFormat::Png).unwrap();
+        let server = MockServer::start().await;
+        let html = r#"<link rel="icon" sizes="16x16" href="/icon.png"><link rel="icon" href="/icon.svg">"#;
+        Mock::given(path("/page")).respond_with(page(html)).mount(&server).await;
+        Mock::given(path("/icon.png"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(png.into_inner(), "image/png"))
+            .mount(&server)
+            .await;
+        Mock::given(path("/icon.svg"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw("<svg/>", "image/svg+xml"))
+            .mount(&server)
+            .await;
+
+        let state = linkdrop(Builder::new().probe_icon_dimensions(true));
+        let meta = resolve_link(&state, &format!("{}/page", server.uri())).await.unwrap();
+        let dimensions = meta.icons[0].dimensions.as_ref().unwrap();
+        assert_eq!((dimensions.width, dimensions.height), (48, 32));
+        assert_eq!(dimensions.format, "png");
+        assert!(meta.icons[1].dimensions.is_none());
+
+        let meta = resolve_link(&linkdrop(Builder::new()), &format!("{}/page", server.uri())).await.unwrap();
+        assert!(meta.icons[0].dimensions.is_none());
+    }
+}
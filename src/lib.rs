@@ -1,141 +1,3650 @@
-use std::{fs, path::{Path, PathBuf}};
-use tauri::{RunEvent, Runtime, WindowEvent, DragDropEvent, Emitter};
+use std::{
+    collections::HashMap,
+    fs,
+    io::Read,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tauri::{AppHandle, PhysicalPosition, RunEvent, Runtime, WindowEvent, DragDropEvent, Emitter, Manager};
+use tauri::ipc::Channel;
 use tauri::plugin::{Builder as PluginBuilder, TauriPlugin};
 
-use serde::Serialize;
+use futures::{stream, Stream, StreamExt};
+#[cfg(feature = "cookies")]
+use reqwest::cookie::Jar;
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT_LANGUAGE, ETAG, IF_NONE_MATCH, RANGE, RETRY_AFTER};
+use reqwest::redirect::Policy;
+use reqwest::{Client, RequestBuilder, Response, StatusCode, Url};
+use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "blocking")]
+mod blocking;
+mod cache;
+mod commands;
+mod error;
+mod extract;
+#[cfg(feature = "icon-dimensions")]
+mod icons;
+mod pool;
+mod seen;
+
+#[cfg(feature = "blocking")]
+pub use blocking::fetch_metadata_blocking;
+use cache::MetadataCache;
+use error::RefusedRedirect;
+pub use error::LinkdropError;
+use extract::{extract, normalize_url, parse_accept_language, CustomSelector, ExtractConfig};
+pub use extract::{extract_metadata, DescriptionStrategy, Fields};
+use pool::WorkerPool;
+use seen::SeenUrls;
+
+/// Whole-request timeout used unless set by [`Builder::timeout`] or `LINKDROP_TIMEOUT_MS`.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// `User-Agent` sent unless set by [`Builder::user_agent`] or `LINKDROP_USER_AGENT`.
+const DEFAULT_USER_AGENT: &str = concat!("tauri-plugin-linkdrop/", env!("CARGO_PKG_VERSION"));
+
+/// Redirects followed per fetch unless set by [`Builder::max_redirects`].
+const DEFAULT_MAX_REDIRECTS: usize = 10;
+
+/// Wall-clock limit on resolving one link, retries and extra requests
+/// included, unless set by [`Builder::deadline`].
+const DEFAULT_DEADLINE: Duration = Duration::from_secs(30);
+
+/// Cache TTL used when caching is enabled without an explicit [`Builder::cache_ttl`].
+const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Drop events arriving within this window of the first one are treated as a
+/// single gesture and share a batch id, unless [`Builder::coalesce_window`]
+/// says otherwise.
+const DEFAULT_COALESCE_WINDOW: Duration = Duration::from_millis(150);
+
+/// Longest [`Builder::coalesce_window`] accepted; longer ones are clamped to it.
+pub(crate) const MAX_COALESCE_WINDOW: Duration = Duration::from_secs(5);
+
+/// Body size limit used unless [`Builder::max_body_size`] is set.
+const DEFAULT_MAX_BODY_SIZE: u64 = 5 * 1024 * 1024;
+
+/// Longest `Retry-After` honoured unless [`Builder::max_retry_after`] is set.
+const DEFAULT_MAX_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+/// How long the `/favicon.ico` check may take unless
+/// [`Builder::favicon_check_timeout`] is set.
+const DEFAULT_FAVICON_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long a [`Builder::oembed`] or [`Builder::web_manifest`] request may take.
+const FOLLOW_UP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Most bytes of oEmbed or manifest JSON read; larger responses are ignored.
+const FOLLOW_UP_MAX_SIZE: u64 = 256 * 1024;
+
+/// Delay before retrying a `429` that has no `Retry-After` header.
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct LinkMetadata {
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+    pub favicon: Option<String>,
+    /// Every icon the page declares, in document order except that Safari
+    /// `mask-icon`s come last. `favicon` is the first.
+    #[serde(default)]
+    pub icons: Vec<IconCandidate>,
+    /// Every preview image the page declares, in document order. `image` is
+    /// the one with the largest declared `og:image:width`/`og:image:height`,
+    /// else the first `og:image`, else the first `twitter:image`. Pages with
+    /// none of those fall back to `<meta itemprop="image">`, then the largest
+    /// candidate of the first `srcset` in the page.
+    #[serde(default)]
+    pub images: Vec<String>,
+    /// The page's own preview image when [`Builder::image_proxy`] rewrote
+    /// `image` to go through the proxy; `None` otherwise.
+    #[serde(default)]
+    pub original_image: Option<String>,
+    /// The page's `<meta name="keywords">` (split at commas) and
+    /// `article:tag` values, in document order without duplicates.
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    /// The author named by the page's oEmbed data; see [`Builder::oembed`].
+    #[serde(default)]
+    pub author: Option<String>,
+    /// Ready-to-embed HTML from the page's oEmbed data; see [`Builder::oembed`].
+    #[serde(default)]
+    pub embed_html: Option<String>,
+    /// The `theme_color` of the page's web app manifest; see
+    /// [`Builder::web_manifest`].
+    #[serde(default)]
+    pub theme_color: Option<String>,
+    /// The page's `rel="canonical"` URL. When [`Builder::unwrap_amp`] replaced
+    /// an AMP page with its canonical article, this is the article fetched.
+    #[serde(default)]
+    pub canonical_url: Option<String>,
+    /// Where the page was actually read from, after redirects and any
+    /// [`Builder::www_fallback`] retry. `None` when nothing was fetched.
+    #[serde(default)]
+    pub final_url: Option<String>,
+    /// Which tag each extracted field came from, e.g. `"title": "og:title"`.
+    /// Only filled in when [`Builder::record_sources`] is on.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub sources: HashMap<String, String>,
+    /// The first 64 KiB of the HTML the metadata was extracted from. Only
+    /// filled in when [`Builder::include_raw_html`] is on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_html: Option<String>,
+    /// The server kept answering `429 Too Many Requests`; try again later.
+    #[serde(default)]
+    pub rate_limited: bool,
+}
+
+impl LinkMetadata {
+    /// The URL-only result emitted when fetching `url` failed with `error`.
+    fn fallback(url: String, error: &LinkdropError) -> Self {
+        Self {
+            url,
+            rate_limited: matches!(error, LinkdropError::RateLimited { .. }),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct IconCandidate {
+    pub url: String,
+    pub rel: String,
+    pub sizes: Option<String>,
+    #[serde(rename = "type")]
+    pub mime_type: Option<String>,
+    /// What the icon's bytes actually are, with
+    /// [`Builder::probe_icon_dimensions`]; `None` otherwise or if they
+    /// couldn't be read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dimensions: Option<IconDimensions>,
+}
+
+/// Size and format read from an icon's image header.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct IconDimensions {
+    pub width: u32,
+    pub height: u32,
+    /// File extension of the detected format, e.g. `"png"` or `"ico"`.
+    pub format: String,
+}
+
+/// An item of [`fetch_metadata_stream`].
+#[derive(Debug)]
+pub enum MetadataUpdate {
+    /// Only `url` is set; sent before any network activity.
+    Partial(LinkMetadata),
+    /// The outcome of fetching the page.
+    Complete(Result<LinkMetadata, LinkdropError>),
+}
+
+/// Which hosts may be fetched; see [`Builder::allow_host`] and
+/// [`Builder::deny_host`].
+#[derive(Clone, Default)]
+struct HostPolicy {
+    allow: Vec<String>,
+    deny: Vec<String>,
+}
+
+impl HostPolicy {
+    /// Denied hosts are refused even when also allowed; an empty allowlist
+    /// allows everything not denied.
+    fn permits(&self, host: &str) -> bool {
+        let host = host.to_ascii_lowercase();
+        let matching = |patterns: &[String]| patterns.iter().any(|p| host_matches(p, &host));
+        !matching(&self.deny) && (self.allow.is_empty() || matching(&self.allow))
+    }
+}
+
+/// Whether `host` is `pattern`, or, for a `*.example.com` pattern, any
+/// subdomain of `example.com` (but not `example.com` itself).
+fn host_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host.strip_suffix(domain).is_some_and(|sub| sub.len() > 1 && sub.ends_with('.')),
+        None => host == pattern,
+    }
+}
+
+/// A username and password for [`Builder::basic_auth`]. Deliberately not
+/// `Debug`, so it can't end up in logs.
+#[derive(Clone)]
+struct Credentials {
+    username: String,
+    password: Option<String>,
+}
+
+/// How much the plugin deals with image and icon URLs; see [`Builder::media`].
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum MediaMode {
+    /// Report them and check for `/favicon.ico`.
+    #[default]
+    Full,
+    /// Report the URLs the page declares, but never request any image or icon.
+    NoRequests,
+    /// Like [`MediaMode::NoRequests`], and leave `image`, `images`, `favicon`
+    /// and `icons` empty too.
+    Omit,
+}
+
+/// Settings applied to every page fetch.
+#[derive(Clone)]
+pub(crate) struct FetchConfig {
+    /// The whole fetch, body reads and follow-up requests included, fails with
+    /// [`LinkdropError::Timeout`] after this long.
+    deadline: Duration,
+    /// Bodies are truncated to this many bytes, and responses advertising a
+    /// larger `Content-Length` are not downloaded at all.
+    max_body_size: u64,
+    head_only: bool,
+    /// How many times a `429` response is retried.
+    rate_limit_retries: u32,
+    /// A `429` asking to wait longer than this is not retried.
+    max_retry_after: Duration,
+    /// Re-fetch AMP pages' canonical article and extract from that instead.
+    unwrap_amp: bool,
+    www_fallback: bool,
+    /// Re-fetch a `rel="canonical"` on another origin and extract from that.
+    follow_cross_origin_canonical: bool,
+    /// Fetch the page's advertised oEmbed endpoint.
+    oembed: bool,
+    /// Fetch the page's `rel="manifest"` web app manifest.
+    web_manifest: bool,
+    /// Check for `/favicon.ico` when a page declares no icon.
+    favicon_fallback: bool,
+    favicon_check_timeout: Duration,
+    #[cfg(feature = "icon-dimensions")]
+    probe_icons: bool,
+    media: MediaMode,
+    /// Template and target width from [`Builder::image_proxy`].
+    image_proxy: Option<(String, u32)>,
+    /// Basic auth credentials by lowercase host; see [`Builder::basic_auth`].
+    credentials: HashMap<String, Credentials>,
+    hosts: HostPolicy,
+    extract: ExtractConfig,
+    on_response: Option<ResponseObserver>,
+}
+
+impl Default for FetchConfig {
+    fn default() -> Self {
+        Self {
+            deadline: DEFAULT_DEADLINE,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            head_only: false,
+            rate_limit_retries: 0,
+            max_retry_after: DEFAULT_MAX_RETRY_AFTER,
+            unwrap_amp: false,
+            www_fallback: false,
+            follow_cross_origin_canonical: false,
+            oembed: false,
+            web_manifest: false,
+            favicon_fallback: true,
+            favicon_check_timeout: DEFAULT_FAVICON_CHECK_TIMEOUT,
+            #[cfg(feature = "icon-dimensions")]
+            probe_icons: false,
+            media: MediaMode::Full,
+            image_proxy: None,
+            credentials: HashMap::new(),
+            hosts: HostPolicy::default(),
+            extract: ExtractConfig::default(),
+            on_response: None,
+        }
+    }
+}
+
+/// Shortcut files larger than this are refused unless
+/// [`Builder::max_shortcut_size`] is set.
+const DEFAULT_MAX_SHORTCUT_SIZE: u64 = 1024 * 1024;
+
+/// Most entries looked at in one dropped directory tree under
+/// [`Builder::directory_drops`], however few of them are shortcuts.
+const MAX_DIRECTORY_ENTRIES: usize = 10_000;
+
+/// Version of the [`LinkDropEvent`] payload shape, bumped on breaking changes.
+pub const LINK_DROP_EVENT_VERSION: u32 = 1;
+
+/// Payload of the `link-dropped` event: the page metadata plus where it came from.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LinkDropEvent {
+    /// Always [`LINK_DROP_EVENT_VERSION`].
+    pub version: u32,
+    pub metadata: LinkMetadata,
+    /// Shared by every link dropped in the same gesture.
+    pub batch_id: u64,
+    /// How many links that gesture contained.
+    pub batch_total: usize,
+    /// The dropped file the URL was read from.
+    pub path: PathBuf,
+    /// Where the file was dropped, in physical pixels relative to the window.
+    pub x: f64,
+    pub y: f64,
+    /// When the drop was handled, in milliseconds since the Unix epoch.
+    pub dropped_at: u64,
+}
+
+/// Settings deciding which dropped files are treated as links.
+#[derive(Clone)]
+pub(crate) struct DropConfig {
+    txt_links: bool,
+    /// Emit `link-drop-unsupported` for files that aren't accepted.
+    report_unsupported: bool,
+    /// Larger shortcut files fail with `ParseFailed` without being read.
+    max_shortcut_size: u64,
+    /// From [`Builder::require_fields`].
+    required: Option<(Fields, Insufficient)>,
+    /// Maximum depth and number of shortcut files for
+    /// [`Builder::directory_drops`].
+    directories: Option<(usize, usize)>,
+    suppress_unfetched: bool,
+}
+
+impl Default for DropConfig {
+    fn default() -> Self {
+        Self {
+            txt_links: false,
+            report_unsupported: false,
+            max_shortcut_size: DEFAULT_MAX_SHORTCUT_SIZE,
+            required: None,
+            directories: None,
+            suppress_unfetched: false,
+        }
+    }
+}
+
+impl DropConfig {
+    /// Lowercased extensions, without the dot, of the files treated as links.
+    fn extensions(&self) -> Vec<&'static str> {
+        let mut extensions = vec!["url", "webloc"];
+        if self.txt_links {
+            extensions.push("txt");
+        }
+        extensions
+    }
+
+    fn accepts(&self, path: &Path) -> bool {
+        extension(path).is_some_and(|ext| self.extensions().contains(&ext.as_str()))
+    }
+
+    /// How to handle `metadata` when it lacks a required field, or `None` to
+    /// emit it as usual.
+    fn insufficient(&self, metadata: &LinkMetadata) -> Option<Insufficient> {
+        let (fields, insufficient) = self.required?;
+        let has = |field, present: bool| !fields.contains(field) || present;
+        let sufficient = has(Fields::TITLE, metadata.title.is_some())
+            && has(Fields::DESCRIPTION, metadata.description.is_some())
+            && has(Fields::IMAGE, metadata.image.is_some())
+            && has(Fields::FAVICON, metadata.favicon.is_some())
+            && has(Fields::KEYWORDS, !metadata.keywords.is_empty());
+        (!sufficient).then_some(insufficient)
+    }
+}
+
+/// One HTTP response received while resolving a link, or a request that got
+/// none; see [`Builder::on_response`].
+#[derive(Clone, Debug)]
+pub struct ResponseAudit {
+    /// The URL this request was sent to.
+    pub url: String,
+    /// `None` when the request failed without a response, e.g. on a timeout.
+    pub status: Option<u16>,
+    /// Body bytes read; `0` for redirects and responses whose body wasn't needed.
+    pub bytes: u64,
+    /// From sending the request to finishing with the response. `None` for
+    /// redirects, which reqwest follows internally.
+    pub elapsed: Option<Duration>,
+}
+
+/// Observes every response; see [`Builder::on_response`].
+type ResponseObserver = Arc<dyn Fn(&ResponseAudit) + Send + Sync>;
+
+/// When a response's request was sent, kept in its extensions for [`audit`].
+#[derive(Clone, Copy)]
+struct SentAt(Instant);
+
+impl FetchConfig {
+    fn observe(&self, url: &Url, status: Option<StatusCode>, bytes: u64, elapsed: Option<Duration>) {
+        if let Some(on_response) = &self.on_response {
+            on_response(&ResponseAudit {
+                url: url.to_string(),
+                status: status.map(|s| s.as_u16()),
+                bytes,
+                elapsed,
+            });
+        }
+    }
+}
+
+/// Report `resp`, with `bytes` of its body read, to [`Builder::on_response`].
+fn audit(resp: &Response, bytes: u64, config: &FetchConfig) {
+    let elapsed = resp.extensions().get::<SentAt>().map(|sent| sent.0.elapsed());
+    config.observe(resp.url(), Some(resp.status()), bytes, elapsed);
+}
+
+/// Decides whether to follow a redirect; see [`Builder::redirect_filter`].
+type RedirectFilter = Arc<dyn Fn(&Url, &Url) -> bool + Send + Sync>;
+
+/// Decides whether a link is fetched at all; see [`Builder::should_fetch`].
+type FetchFilter = Arc<dyn Fn(&Url) -> bool + Send + Sync>;
+
+/// Rewrites a link's URL before it is fetched; see [`Builder::rewrite_url`].
+type UrlRewrite = Arc<dyn Fn(&Url) -> Url + Send + Sync>;
+
+/// Turns a failure into the `error` of `link-drop-error`; see [`Builder::map_error`].
+type ErrorMapper = Arc<dyn Fn(&LinkdropError) -> serde_json::Value + Send + Sync>;
+
+/// The default [`Builder::map_error`]: `{ code, message }`.
+fn default_error_payload(error: &LinkdropError) -> serde_json::Value {
+    serde_json::json!({ "code": error.code(), "message": error.to_string() })
+}
+
+/// Payload of the `link-drop-error` event.
 #[derive(Serialize, Clone)]
-struct LinkMetadata {
+struct DropError {
+    path: PathBuf,
+    /// `null` when the shortcut itself couldn't be read.
+    url: Option<String>,
+    batch_id: u64,
+    error: serde_json::Value,
+}
+
+/// Payload of the `link-duplicate` event, sent instead of `link-dropped` for a
+/// link already handled this session when [`Builder::skip_seen_urls`] is on.
+#[derive(Serialize, Clone)]
+struct DuplicateLink {
     url: String,
-    title: Option<String>,
-    description: Option<String>,
-    image: Option<String>,
-    favicon: Option<String>,
+    batch_id: u64,
 }
 
-pub fn init<R: Runtime>() -> TauriPlugin<R> {
-    PluginBuilder::new("linkdrop")
-        .on_event(|app_handle, event| {
-            if let RunEvent::WindowEvent { event, .. } = event {
-                if let WindowEvent::DragDrop(file_event) = event {
-                    if let DragDropEvent::Drop { paths, .. } = file_event {
-                        if let Some(first_path) = paths.first() {
-                            let path_buf = first_path.clone();
-                            let app = app_handle.clone();
-                            std::thread::spawn(move || {
-                                if let Some(meta) = handle_dropped_file(&path_buf) {
-                                    let _ = app.emit("link-dropped", meta);
-                                }
-                            });
-                        }
-                    }
-                }
-            }
-        })
-        .build()
-}
-
-fn handle_dropped_file(path: &Path) -> Option<LinkMetadata> {
-    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
-    if ext == "webloc" || ext == "url" {
-        if let Ok(url) = parse_shortcut(path, &ext) {
-            match fetch_metadata(&url) {
-                Ok(meta) => return Some(meta),
-                Err(_) => {
-                    return Some(LinkMetadata {
-                        url,
-                        title: None,
-                        description: None,
-                        image: None,
-                        favicon: None,
-                    });
-                }
+/// Payload of the `link-drop-unsupported` event.
+#[derive(Serialize, Clone)]
+struct UnsupportedDrop {
+    path: PathBuf,
+    /// Lowercased, without the dot; `null` when the file has none.
+    extension: Option<String>,
+}
+
+/// How keys are spelled in the JSON of emitted events; see
+/// [`Builder::field_naming`].
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum FieldNaming {
+    /// `canonical_url`, `batch_id`, … as the Rust structs spell them.
+    #[default]
+    SnakeCase,
+    /// `canonicalUrl`, `batchId`, … for JavaScript-style frontends.
+    CamelCase,
+}
+
+/// Payload fields whose values are left as they are, not respelled: the
+/// [`Builder::map_error`] payload is the app's own, and the keys of
+/// [`LinkMetadata::sources`] are data rather than field names.
+const VERBATIM_FIELDS: &[&str] = &["error", "sources"];
+
+impl FieldNaming {
+    fn apply(self, value: serde_json::Value) -> serde_json::Value {
+        use serde_json::Value;
+        match (self, value) {
+            (Self::SnakeCase, value) => value,
+            (Self::CamelCase, Value::Object(map)) => Value::Object(
+                map.into_iter()
+                    .map(|(key, value)| {
+                        let value = if VERBATIM_FIELDS.contains(&key.as_str()) { value } else { self.apply(value) };
+                        (camel_case(&key), value)
+                    })
+                    .collect(),
+            ),
+            (Self::CamelCase, Value::Array(items)) => {
+                Value::Array(items.into_iter().map(|item| self.apply(item)).collect())
             }
+            (Self::CamelCase, value) => value,
         }
     }
-    None
 }
 
-fn parse_shortcut(path: &Path, ext: &str) -> Result<String, Box<dyn std::error::Error>> {
-    let content = fs::read_to_string(path)?;
-    if ext == "url" {
-        for line in content.lines() {
-            if let Some(rest) = line.strip_prefix("URL=") {
-                return Ok(rest.trim().to_string());
-            }
-        }
-    } else if ext == "webloc" {
-        // crude XML/plist parsing for <string>URL</string>
-        if let Some(start) = content.find("<string>") {
-            let after = start + "<string>".len();
-            if let Some(end) = content[after..].find("</string>") {
-                let url = &content[after..after + end];
-                return Ok(url.trim().to_string());
-            }
+/// What happens to a dropped link missing a field required by
+/// [`Builder::require_fields`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Insufficient {
+    /// Emit `link-drop-insufficient`, with the `link-dropped` payload, instead.
+    Report,
+    /// Emit nothing but any `link-drop-error`.
+    Suppress,
+}
+
+/// Where event payloads are sent; see [`Builder::delivery`].
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum Delivery {
+    /// Global Tauri events, listened to with `listen("link-dropped", …)`.
+    #[default]
+    Event,
+    /// Only the channels frontends passed to `plugin:linkdrop|subscribe`.
+    Channel,
+    /// Both of the above.
+    Both,
+}
+
+fn camel_case(key: &str) -> String {
+    let mut words = key.split('_');
+    let mut camel = words.next().unwrap_or_default().to_string();
+    for word in words {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            camel.extend(first.to_uppercase());
+            camel.push_str(chars.as_str());
         }
     }
-    Err("Failed to parse shortcut file".into())
+    camel
 }
 
-fn fetch_metadata(url: &str) -> Result<LinkMetadata, Box<dyn std::error::Error>> {
-    let resp = reqwest::blocking::get(url)?;
-    let base_url = resp.url().clone();
-    let html = resp.text()?;
+/// What handling one dropped file produced.
+enum DropOutcome {
+    /// The link's metadata, URL-only if fetching failed with the error.
+    Link(Box<LinkMetadata>, Option<LinkdropError>),
+    Duplicate(String),
+    /// The shortcut file couldn't be read or held no URL.
+    Unreadable(LinkdropError),
+}
+
+/// A dropped path and where in the window it was dropped.
+type DroppedPath = (PathBuf, PhysicalPosition<f64>);
+
+/// Plugin state shared by every drop handled by this app.
+struct Linkdrop {
+    client: Client,
+    fetch: FetchConfig,
+    drop: DropConfig,
+    should_fetch: Option<FetchFilter>,
+    rewrite_url: Option<UrlRewrite>,
+    map_error: ErrorMapper,
+    field_naming: FieldNaming,
+    delivery: Delivery,
+    /// Channels registered through `subscribe`.
+    channels: Mutex<Vec<Channel<serde_json::Value>>>,
+    cache: Option<MetadataCache>,
+    pool: Option<WorkerPool>,
+    seen: Option<SeenUrls>,
+    /// Paths, and where they were dropped, collected for the drop gesture
+    /// currently being coalesced.
+    pending_drop: Mutex<Option<Vec<DroppedPath>>>,
+    next_batch_id: AtomicU64,
+    /// In-flight `fetch_link` calls, by id.
+    fetches: Mutex<HashMap<u64, tauri::async_runtime::JoinHandle<()>>>,
+    next_fetch_id: AtomicU64,
+    /// See [`Builder::coalesce_window`]; adjustable at runtime.
+    pub(crate) coalesce_window_ms: AtomicU64,
+}
+
+impl Linkdrop {
+    /// Whether [`Builder::should_fetch`] lets `url` be fetched.
+    fn should_fetch(&self, url: &Url) -> bool {
+        self.should_fetch.as_ref().is_none_or(|should_fetch| should_fetch(url))
+    }
 
-    let document = scraper::Html::parse_document(&html);
+    /// `payload` as JSON with keys spelled per [`Builder::field_naming`].
+    fn to_json(&self, payload: impl Serialize) -> serde_json::Result<serde_json::Value> {
+        serde_json::to_value(payload).map(|value| self.field_naming.apply(value))
+    }
 
-    // Title
-    let mut title = None;
-    if let Some(elem) = document.select(&scraper::Selector::parse("title").unwrap()).next() {
-        let text: String = elem.text().collect();
-        if !text.trim().is_empty() {
-            title = Some(text.trim().to_string());
+    /// Deliver `event` to the frontend as [`Builder::delivery`] says, with keys
+    /// spelled per [`Builder::field_naming`].
+    fn emit<R: Runtime>(&self, app: &AppHandle<R>, event: &str, payload: impl Serialize) {
+        let Ok(value) = self.to_json(payload) else { return };
+        if self.delivery != Delivery::Event {
+            let message = serde_json::json!({ "event": event, "payload": value });
+            // A channel whose webview has gone away fails to send; forget it.
+            self.channels
+                .lock()
+                .unwrap()
+                .retain(|channel| channel.send(message.clone()).is_ok());
+        }
+        if self.delivery != Delivery::Channel {
+            let _ = app.emit(event, value);
         }
     }
-    if title.is_none() {
-        if let Some(meta) = document.select(&scraper::Selector::parse(r#"meta[property=\"og:title\"]"#).unwrap()).next() {
-            if let Some(content) = meta.value().attr("content") {
-                title = Some(content.to_string());
+
+    /// Run `job` on the worker pool when one is configured, blocking while its
+    /// queue is full, or as a task of its own otherwise.
+    fn run(&self, job: impl std::future::Future<Output = ()> + Send + 'static) {
+        match &self.pool {
+            Some(pool) => pool.submit(job),
+            None => {
+                tauri::async_runtime::spawn(job);
             }
         }
     }
+}
 
-    // Description
-    let mut description = None;
-    for sel in &[r#"meta[name=\"description\"]"#, r#"meta[property=\"og:description\"]"#] {
-        if let Some(meta) = document.select(&scraper::Selector::parse(sel).unwrap()).next() {
-            if let Some(content) = meta.value().attr("content") {
-                description = Some(content.to_string());
-                break;
+/// Configures the linkdrop plugin before it is registered with the app.
+#[derive(Default)]
+pub struct Builder {
+    #[cfg(feature = "cookies")]
+    cookie_store: bool,
+    #[cfg(feature = "cookies")]
+    cookies: Vec<(Url, String)>,
+    host_overrides: Vec<(String, SocketAddr)>,
+    accept_language: Option<String>,
+    timeout: Option<Duration>,
+    user_agent: Option<String>,
+    max_redirects: Option<usize>,
+    redirect_filter: Option<RedirectFilter>,
+    cache_ttl: Option<Duration>,
+    persist_cache: bool,
+    fetch: FetchConfig,
+    drop: DropConfig,
+    worker_pool: Option<usize>,
+    should_fetch: Option<FetchFilter>,
+    rewrite_url: Option<UrlRewrite>,
+    map_error: Option<ErrorMapper>,
+    field_naming: FieldNaming,
+    delivery: Delivery,
+    skip_seen_urls: bool,
+    seen_urls_limit: Option<usize>,
+    coalesce_window: Option<Duration>,
+    field_selectors: Vec<(Fields, String, Option<String>)>,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep cookies set by fetched pages and send them back on later fetches to
+    /// the same host. Off by default.
+    ///
+    /// Privacy: the jar lives for as long as the app runs, so any site whose
+    /// link is dropped can recognise later drops of its links, and session
+    /// cookies seeded with [`Builder::cookie`] are sent to that site on every
+    /// fetch.
+    #[cfg(feature = "cookies")]
+    pub fn cookie_store(mut self, enabled: bool) -> Self {
+        self.cookie_store = enabled;
+        self
+    }
+
+    /// Seed the cookie jar with a `Set-Cookie` style value for `url`.
+    /// Implies [`Builder::cookie_store`].
+    #[cfg(feature = "cookies")]
+    pub fn cookie(mut self, url: Url, cookie: impl Into<String>) -> Self {
+        self.cookie_store = true;
+        self.cookies.push((url, cookie.into()));
+        self
+    }
+
+    /// Connect to `addr` whenever a fetch targets `host`, bypassing normal DNS
+    /// resolution for that host. The port in `addr` is ignored in favour of the
+    /// URL's port.
+    pub fn resolve(mut self, host: impl Into<String>, addr: SocketAddr) -> Self {
+        self.host_overrides.push((host.into(), addr));
+        self
+    }
+
+    /// Follow at most `max` redirects per fetch; a longer chain fails with
+    /// [`LinkdropError::TooManyRedirects`]. Defaults to 10.
+    pub fn max_redirects(mut self, max: usize) -> Self {
+        self.max_redirects = Some(max);
+        self
+    }
+
+    /// Follow a redirect only if `filter(from, to)` returns `true`, e.g.
+    /// `|from, to| !(from.scheme() == "https" && to.scheme() == "http")` to
+    /// refuse downgrades. Consulted for every hop, before the
+    /// [`Builder::max_redirects`] limit; a refused hop fails the fetch with
+    /// [`LinkdropError::RedirectRefused`], carrying the chain up to it, and
+    /// the link is emitted URL-only. Applies to every request the plugin
+    /// makes. By default every redirect is followed.
+    pub fn redirect_filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&Url, &Url) -> bool + Send + Sync + 'static,
+    {
+        self.redirect_filter = Some(Arc::new(filter));
+        self
+    }
+
+    /// Call `on_response` for every HTTP response received while resolving a
+    /// link, successful or not: each redirect hop, the page itself including
+    /// retried attempts, and the AMP, canonical, oEmbed, manifest and
+    /// `/favicon.ico` follow-ups. Requests that fail without a response are
+    /// reported with no status. Meant for audit logs; it runs on the fetching
+    /// task, so keep it quick. `check_link` reports its requests the same way.
+    pub fn on_response<F>(mut self, on_response: F) -> Self
+    where
+        F: Fn(&ResponseAudit) + Send + Sync + 'static,
+    {
+        self.fetch.on_response = Some(Arc::new(on_response));
+        self
+    }
+
+    /// Send HTTP Basic credentials on every request to exactly `host` (no
+    /// subdomains), so pages behind basic auth can be read. Other hosts never
+    /// get them, and reqwest drops them when a redirect leaves `host`.
+    ///
+    /// Security: Basic auth is only base64, so over plain `http` the password
+    /// is readable by anyone on the network path; prefer `https` hosts. Any
+    /// link to `host` dropped on the app is fetched with these credentials,
+    /// including links an untrusted page tricks the user into dropping. The
+    /// plugin never logs or emits them.
+    pub fn basic_auth(mut self, host: impl Into<String>, username: impl Into<String>, password: Option<String>) -> Self {
+        let credentials = Credentials {
+            username: username.into(),
+            password,
+        };
+        self.fetch.credentials.insert(host.into().to_ascii_lowercase(), credentials);
+        self
+    }
+
+    /// Only fetch links whose host matches one of the allowed patterns. A
+    /// pattern is an exact host, or `*.example.com` for any subdomain of
+    /// `example.com` (add `example.com` too to allow the bare domain). Until a
+    /// host is allowed, every host is. Links to other hosts fail with
+    /// [`LinkdropError::Blocked`] and are emitted URL-only.
+    ///
+    /// Checked against the link after [`Builder::rewrite_url`], not against
+    /// redirects, canonical pages or subresources such as the favicon.
+    pub fn allow_host(mut self, pattern: impl Into<String>) -> Self {
+        self.fetch.hosts.allow.push(pattern.into().to_ascii_lowercase());
+        self
+    }
+
+    /// Never fetch links whose host matches `pattern`, written as for
+    /// [`Builder::allow_host`]. The denylist wins: a host matching both is
+    /// blocked.
+    pub fn deny_host(mut self, pattern: impl Into<String>) -> Self {
+        self.fetch.hosts.deny.push(pattern.into().to_ascii_lowercase());
+        self
+    }
+
+    /// Give up on a page fetch after `timeout`.
+    ///
+    /// Precedence: this setting, then the `LINKDROP_TIMEOUT_MS` environment
+    /// variable (milliseconds) read when the plugin is set up, then 15 seconds.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Give up on resolving a link after `deadline` in total, however the time
+    /// is spent: a body trickling in slowly, `429` retries, or the AMP, oEmbed
+    /// and `/favicon.ico` follow-ups. The link then gets URL-only metadata.
+    /// Defaults to 30 seconds; [`Builder::timeout`] still bounds each request.
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.fetch.deadline = deadline;
+        self
+    }
+
+    /// The `User-Agent` header sent with every fetch.
+    ///
+    /// Precedence: this setting, then the `LINKDROP_USER_AGENT` environment
+    /// variable read when the plugin is set up, then
+    /// `tauri-plugin-linkdrop/<version>`.
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Send `Accept-Language: <value>` with every fetch (e.g. `"fr-CH, fr;q=0.9, en;q=0.8"`)
+    /// and prefer the matching `og:title`/`og:description` when a page offers
+    /// several. A page with only one localized value keeps its usual title and
+    /// description.
+    ///
+    /// A tag's locale is its `lang` attribute or, when a page has exactly as
+    /// many tags as `og:locale` plus `og:locale:alternate` entries, the locale
+    /// in the same position. Languages are tried in `q` order; each first looks
+    /// for an exact match (`fr-FR` = `fr_FR`) and then for the same primary
+    /// language (`fr` = `fr_CA`). Without a match the usual title and
+    /// description are kept.
+    pub fn accept_language(mut self, value: impl Into<String>) -> Self {
+        let value = value.into();
+        self.fetch.extract.languages = parse_accept_language(&value);
+        self.accept_language = Some(value);
+        self
+    }
+
+    /// Cache fetched metadata in memory for `ttl`. Once an entry is stale it is
+    /// revalidated with its `ETag` (if the server sent one) before re-fetching.
+    pub fn cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache_ttl = Some(ttl);
+        self
+    }
+
+    /// Persist the metadata cache across restarts. Enables the cache with a
+    /// one hour TTL unless [`Builder::cache_ttl`] is set. Off by default.
+    ///
+    /// Entries are stored as JSON in `linkdrop/metadata-cache.json` under the
+    /// app's cache directory (`PathResolver::app_cache_dir`), as
+    /// `{"version": 1, "entries": {"<url>": {"metadata": {..}, "etag": .., "fetched_at": <unix ms>}}}`.
+    /// The file is loaded when the plugin is set up and written at most once a
+    /// minute while links are fetched, and again when the app exits.
+    pub fn persist_cache(mut self, enabled: bool) -> Self {
+        self.persist_cache = enabled;
+        self
+    }
+
+    /// Maximum number of body bytes read from a page; defaults to 5 MiB. Pages
+    /// whose `Content-Length` exceeds it are skipped without downloading and
+    /// fall back to a URL-only result. Without a `Content-Length`, the body is
+    /// read up to the limit and the truncated HTML is parsed.
+    pub fn max_body_size(mut self, bytes: u64) -> Self {
+        self.fetch.max_body_size = bytes;
+        self
+    }
+
+    /// Stop downloading a page as soon as its `</head>` arrives and extract
+    /// from just that prefix, saving bandwidth and parse time on large pages.
+    /// A page without a `</head>` is read in full as usual, failing with
+    /// [`LinkdropError::TooLarge`] when it's bigger than
+    /// [`Builder::max_body_size`]. Fallbacks that look in the body, such as
+    /// [`Builder::paragraph_description`], `<img srcset>` images and most of
+    /// [`Builder::spa_title`], then find nothing. Off by default.
+    pub fn head_only(mut self, enabled: bool) -> Self {
+        self.fetch.head_only = enabled;
+        self
+    }
+
+    /// Treat dropped `.txt` files whose first non-empty line is an http(s) URL
+    /// as links. Other `.txt` files are ignored. Off by default.
+    pub fn txt_links(mut self, enabled: bool) -> Self {
+        self.drop.txt_links = enabled;
+        self
+    }
+
+    /// Handle a dropped folder as a drop of the shortcut files inside it, in
+    /// one batch at the folder's drop position. Subfolders are searched down to
+    /// `max_depth` levels (`0` for just the folder itself) and at most
+    /// `max_files` shortcuts are taken, in name order. Symlinked folders are
+    /// skipped, so loops can't trap the walk, and each dropped folder is
+    /// searched through at most 10,000 entries. Off by default.
+    pub fn directory_drops(mut self, max_depth: usize, max_files: usize) -> Self {
+        self.drop.directories = Some((max_depth, max_files));
+        self
+    }
+
+    /// Refuse shortcut files larger than `bytes` with
+    /// [`LinkdropError::ParseFailed`] instead of reading them. Real shortcuts
+    /// are a few hundred bytes; this only guards against huge files posing as
+    /// one. Defaults to 1 MiB.
+    pub fn max_shortcut_size(mut self, bytes: u64) -> Self {
+        self.drop.max_shortcut_size = bytes;
+        self
+    }
+
+    /// Fetch dropped links on `size` dedicated threads instead of Tauri's async
+    /// runtime. At most `size` links are fetched at once and at most `size`
+    /// more wait in the queue; beyond that, handling of further drops blocks
+    /// until a worker frees up. This is then the only bound on simultaneous
+    /// drop fetches. Without a pool every dropped link is fetched concurrently.
+    pub fn worker_pool(mut self, size: usize) -> Self {
+        self.worker_pool = Some(size);
+        self
+    }
+
+    /// Treat drop events arriving within `window` of the first one as a single
+    /// gesture, sharing a batch id. Too short splits a multi-file drop the
+    /// platform delivers in several events into several batches; too long
+    /// delays every drop's first result by that much. Defaults to 150 ms on
+    /// every platform: Tauri normally delivers a multi-file drop as one event,
+    /// so the window only has to absorb the odd follow-up event. Windows over
+    /// 5 seconds are clamped to 5 seconds. The frontend can read and change it
+    /// with `plugin:linkdrop|coalesce_window`.
+    pub fn coalesce_window(mut self, window: Duration) -> Self {
+        self.coalesce_window = Some(window.min(MAX_COALESCE_WINDOW));
+        self
+    }
+
+    /// Only extract these fields, leaving the others empty and skipping the
+    /// work for them, including the `/favicon.ico` check when
+    /// [`Fields::FAVICON`] is left out. Defaults to [`Fields::ALL`].
+    pub fn fields(mut self, fields: Fields) -> Self {
+        self.fetch.extract.fields = fields;
+        self
+    }
+
+    /// How to pick the description when a page declares several. Defaults to
+    /// [`DescriptionStrategy::First`].
+    pub fn description_strategy(mut self, strategy: DescriptionStrategy) -> Self {
+        self.fetch.extract.description_strategy = strategy;
+        self
+    }
+
+    /// When a page has no description tag, use its first substantial
+    /// paragraph (from `<article>`, then `<main>`, then the whole body), cut to
+    /// about 300 characters. Off by default.
+    pub fn paragraph_description(mut self, enabled: bool) -> Self {
+        self.fetch.extract.paragraph_description = enabled;
+        self
+    }
+
+    /// When a page, typically a client-rendered app, has no title in any tag,
+    /// look for one in its `<noscript>` fallback (a `<title>` or `<h1>`) and
+    /// then in the top levels of inline `<script type="application/json">`
+    /// state. Off by default.
+    pub fn spa_title(mut self, enabled: bool) -> Self {
+        self.fetch.extract.spa_title = enabled;
+        self
+    }
+
+    /// Fall back to `selector` for `field` (or each of several combined with
+    /// `|`) when none of the built-in sources has it: the first match's
+    /// `attribute`, or its text when `None`. Meant for known internal sites
+    /// with non-standard markup, e.g. `field_selector(Fields::TITLE,
+    /// ".doc-header h2", None)`. Selectors for the same field are tried in the
+    /// order given; image and favicon values are resolved against the page
+    /// URL and `keywords` are split on commas.
+    ///
+    /// Selectors are checked when the plugin is set up, which fails with an
+    /// error naming the first invalid one.
+    pub fn field_selector(mut self, field: Fields, selector: impl Into<String>, attribute: Option<&str>) -> Self {
+        self.field_selectors.push((field, selector.into(), attribute.map(str::to_string)));
+        self
+    }
+
+    /// Record in [`LinkMetadata::sources`] which tag produced each field. Meant
+    /// for debugging extraction; off by default.
+    pub fn record_sources(mut self, enabled: bool) -> Self {
+        self.fetch.extract.record_sources = enabled;
+        self
+    }
+
+    /// Consult `should_fetch` before spending a request on a link, to skip
+    /// ones the app already has or its user opted out of. It runs once per
+    /// link, after it is parsed and known to be http(s), before
+    /// [`Builder::rewrite_url`], the host lists and the cache, for dropped
+    /// links and the `fetch_link`, `fetch_links` and `prefetch` commands
+    /// alike. Links it returns `false` for aren't fetched and come out
+    /// URL-only without an error, or not at all for drops with
+    /// [`Builder::suppress_unfetched`]. It's called on the fetching task, so
+    /// keep it quick.
+    pub fn should_fetch<F>(mut self, should_fetch: F) -> Self
+    where
+        F: Fn(&Url) -> bool + Send + Sync + 'static,
+    {
+        self.should_fetch = Some(Arc::new(should_fetch));
+        self
+    }
+
+    /// Emit nothing for a dropped link [`Builder::should_fetch`] turned down,
+    /// instead of a URL-only `link-dropped`. Off by default.
+    pub fn suppress_unfetched(mut self, enabled: bool) -> Self {
+        self.drop.suppress_unfetched = enabled;
+        self
+    }
+
+    /// Rewrite each dropped link before it is fetched, e.g. to strip AMP or
+    /// swap a mobile host for the desktop one. Runs after the URL is parsed and
+    /// its scheme checked to be http(s). The emitted `url` is still the one
+    /// read from the dropped file.
+    pub fn rewrite_url(mut self, rewrite: impl Fn(&Url) -> Url + Send + Sync + 'static) -> Self {
+        self.rewrite_url = Some(Arc::new(rewrite));
+        self
+    }
+
+    /// Retry a `429 Too Many Requests` up to `retries` times, waiting as long
+    /// as its `Retry-After` header asks (in seconds or as an HTTP date), or one
+    /// second without one. Once retries run out, or the server asks for more
+    /// than [`Builder::max_retry_after`], the link is emitted URL-only with
+    /// `rate_limited: true`. Defaults to no retries.
+    pub fn rate_limit_retries(mut self, retries: u32) -> Self {
+        self.fetch.rate_limit_retries = retries;
+        self
+    }
+
+    /// Longest `Retry-After` wait [`Builder::rate_limit_retries`] will sit
+    /// through; defaults to 30 seconds.
+    pub fn max_retry_after(mut self, wait: Duration) -> Self {
+        self.fetch.max_retry_after = wait;
+        self
+    }
+
+    /// When a page declares no icon, `HEAD` its site's `/favicon.ico` and use
+    /// it if the server has one. On by default.
+    pub fn favicon_fallback(mut self, enabled: bool) -> Self {
+        self.fetch.favicon_fallback = enabled;
+        self
+    }
+
+    /// How long the [`Builder::favicon_fallback`] check may take, independent
+    /// of the page fetch; defaults to 2 seconds. A check that times out still
+    /// reports `/favicon.ico`, just unconfirmed.
+    pub fn favicon_check_timeout(mut self, timeout: Duration) -> Self {
+        self.fetch.favicon_check_timeout = timeout;
+        self
+    }
+
+    /// Download the first 64 KiB of every icon candidate, a few at a time,
+    /// and record its real pixel size and format in
+    /// [`IconCandidate::dimensions`], so the frontend can pick the sharpest
+    /// instead of trusting `sizes`. Each download is bounded by
+    /// [`Builder::favicon_check_timeout`]. Off by default; ignored unless
+    /// [`MediaMode::Full`].
+    #[cfg(feature = "icon-dimensions")]
+    pub fn probe_icon_dimensions(mut self, enabled: bool) -> Self {
+        self.fetch.probe_icons = enabled;
+        self
+    }
+
+    /// Keep the plugin away from image and icon URLs, for privacy: with
+    /// [`MediaMode::NoRequests`] the page (and any opted-in AMP, oEmbed or
+    /// manifest document) is still fetched but no image or icon ever is, so
+    /// there is no `/favicon.ico` check; [`MediaMode::Omit`] also leaves the
+    /// image and icon fields empty. Defaults to [`MediaMode::Full`].
+    pub fn media(mut self, mode: MediaMode) -> Self {
+        self.fetch.media = mode;
+        self
+    }
+
+    /// Emit `image` through a resizing proxy, e.g.
+    /// `image_proxy("https://img.example.com/{width}/{url}", 400)`.
+    /// `{width}` becomes `width` and `{url}` the page's image URL,
+    /// percent-encoded; the unproxied URL is kept in
+    /// [`LinkMetadata::original_image`]. `images`, icons and images that
+    /// aren't http(s) URLs, such as `data:` URLs, are left alone. Unset by
+    /// default, emitting the raw image URL.
+    pub fn image_proxy(mut self, template: impl Into<String>, width: u32) -> Self {
+        self.fetch.image_proxy = Some((template.into(), width));
+        self
+    }
+
+    /// When connecting to a link's host fails, e.g. because its name doesn't
+    /// resolve, retry once with the `www.` prefix added, or removed if it has
+    /// one, before giving up. [`LinkMetadata::final_url`] then shows the host
+    /// that answered; `url` stays the dropped link. Not tried for IP
+    /// addresses or when [`Builder::allow_host`]/[`Builder::deny_host`] refuse
+    /// the other host. Off by default, since it fetches a host the link
+    /// didn't name.
+    pub fn www_fallback(mut self, enabled: bool) -> Self {
+        self.fetch.www_fallback = enabled;
+        self
+    }
+
+    /// When a page is AMP (`<html amp>`/`<html ⚡>`) and its `rel="canonical"`
+    /// points elsewhere, fetch the canonical article and use its metadata,
+    /// which is usually richer. `url` stays the AMP link and `canonical_url`
+    /// records the article. The canonical has to be an http(s) URL on the AMP
+    /// page's own host or a public one, never `localhost` or a loopback,
+    /// private or link-local IP address elsewhere. Only one extra request is
+    /// made per link; if it fails the AMP page's metadata is used. Off by
+    /// default.
+    pub fn unwrap_amp(mut self, enabled: bool) -> Self {
+        self.fetch.unwrap_amp = enabled;
+        self
+    }
+
+    /// When a page's `rel="canonical"` is on a different origin, as with
+    /// syndicated articles, fetch the canonical page and use its metadata.
+    /// `url` stays the dropped link and `canonical_url` records the page
+    /// fetched. The canonical has to be an http(s) URL and may not point at
+    /// `localhost` or a loopback, private or link-local IP address, since the
+    /// page chooses it. One extra request at most; if it fails the original
+    /// page's metadata is used. Off by default.
+    pub fn follow_cross_origin_canonical(mut self, enabled: bool) -> Self {
+        self.fetch.follow_cross_origin_canonical = enabled;
+        self
+    }
+
+    /// When a page advertises a JSON oEmbed endpoint
+    /// (`<link rel="alternate" type="application/json+oembed">`), fetch it to
+    /// fill in `author` and `embed_html`, plus `title` and `image` (from the
+    /// thumbnail) when the page itself had none and [`Builder::fields`]
+    /// includes them; [`Builder::record_sources`] names `oembed` as the source
+    /// of each. This is one extra request per link, limited to 5 seconds and
+    /// 256 KiB; if it fails the page's metadata is used as is. Since the page
+    /// chooses the endpoint, it has to be an http(s) URL on the page's own host
+    /// or a public one, never `localhost` or a loopback, private or link-local
+    /// IP address elsewhere. Off by default.
+    pub fn oembed(mut self, enabled: bool) -> Self {
+        self.fetch.oembed = enabled;
+        self
+    }
+
+    /// Build the `error` field of `link-drop-error` events with `map_error`
+    /// instead of the default `{ code, message }`, where `code` is
+    /// [`LinkdropError::code`] and `message` its `Display` text.
+    pub fn map_error<F>(mut self, map_error: F) -> Self
+    where
+        F: Fn(&LinkdropError) -> serde_json::Value + Send + Sync + 'static,
+    {
+        self.map_error = Some(Arc::new(map_error));
+        self
+    }
+
+    /// Spell the keys of every emitted event payload, including the nested
+    /// [`LinkMetadata`], and of the metadata `fetch_links` resolves with, in
+    /// this style. Only the plugin's own field names are respelled: the
+    /// [`Builder::map_error`] payload and the keys of
+    /// [`LinkMetadata::sources`] are sent as they are. Defaults to
+    /// [`FieldNaming::SnakeCase`], matching the Rust field names.
+    pub fn field_naming(mut self, naming: FieldNaming) -> Self {
+        self.field_naming = naming;
+        self
+    }
+
+    /// When a page links a web app manifest (`<link rel="manifest">`), fetch
+    /// it to fill in `theme_color`, use its `name` when the page has no title,
+    /// and add its icons to `icons` (after the page's own). Same limits and
+    /// address rules as [`Builder::oembed`]. Off by default.
+    pub fn web_manifest(mut self, enabled: bool) -> Self {
+        self.fetch.web_manifest = enabled;
+        self
+    }
+
+    /// How events reach the frontend. [`Delivery::Event`] (the default) emits
+    /// global Tauri events. [`Delivery::Channel`] instead sends
+    /// `{ event, payload }` messages to every channel registered with
+    /// `plugin:linkdrop|subscribe`, for apps that route results per window.
+    /// [`Delivery::Both`] does both.
+    pub fn delivery(mut self, delivery: Delivery) -> Self {
+        self.delivery = delivery;
+        self
+    }
+
+    /// Process each URL at most once per session. Dropping a link that was
+    /// already handled (compared after dropping fragments and sorting the
+    /// query) emits `link-duplicate` with `{ url, batch_id }` instead of
+    /// fetching it again. Off by default; `reset_seen_urls` forgets them.
+    ///
+    /// Every distinct URL stays in memory until the app exits unless
+    /// [`Builder::seen_urls_limit`] is set.
+    pub fn skip_seen_urls(mut self, enabled: bool) -> Self {
+        self.skip_seen_urls = enabled;
+        self
+    }
+
+    /// Remember at most `limit` URLs for [`Builder::skip_seen_urls`], forgetting
+    /// the oldest first, so a URL older than that may be processed again.
+    pub fn seen_urls_limit(mut self, limit: usize) -> Self {
+        self.seen_urls_limit = Some(limit);
+        self
+    }
+
+    /// Attach the first 64 KiB of the fetched HTML to [`LinkMetadata::raw_html`]
+    /// for diagnosing extraction. Off by default as it bloats every payload.
+    pub fn include_raw_html(mut self, enabled: bool) -> Self {
+        self.fetch.extract.include_raw_html = enabled;
+        self
+    }
+
+    /// Emit `link-drop-unsupported` with `{ path, extension }` for each dropped
+    /// file that isn't a link this plugin handles, so apps can say so. Off by
+    /// default.
+    pub fn report_unsupported(mut self, enabled: bool) -> Self {
+        self.drop.report_unsupported = enabled;
+        self
+    }
+
+    /// Only emit `link-dropped` for links whose metadata has every field in
+    /// `fields`, e.g. `Fields::TITLE` to avoid bare-URL cards; the others are
+    /// handled as `insufficient` says. By default every link is emitted.
+    pub fn require_fields(mut self, fields: Fields, insufficient: Insufficient) -> Self {
+        self.drop.required = Some((fields, insufficient));
+        self
+    }
+
+    pub fn build<R: Runtime>(self) -> TauriPlugin<R> {
+        PluginBuilder::new("linkdrop")
+            .invoke_handler(tauri::generate_handler![
+                commands::clear_cache,
+                commands::parse_shortcut,
+                commands::fetch_link,
+                commands::fetch_links,
+                commands::cancel_fetch,
+                commands::check_link,
+                commands::prefetch,
+                commands::reset_seen_urls,
+                commands::subscribe,
+                commands::supported_extensions,
+                commands::coalesce_window,
+            ])
+            .setup(move |app, _api| {
+                let cache = if self.persist_cache {
+                    let ttl = self.cache_ttl.unwrap_or(DEFAULT_CACHE_TTL);
+                    let path = app.path().app_cache_dir()?.join("linkdrop").join("metadata-cache.json");
+                    Some(MetadataCache::persistent(ttl, path))
+                } else {
+                    self.cache_ttl.map(MetadataCache::new)
+                };
+                app.manage(self.state(cache)?);
+                Ok(())
+            })
+            .on_event(|app_handle, event| match event {
+                RunEvent::WindowEvent {
+                    event: WindowEvent::DragDrop(DragDropEvent::Drop { paths, position }),
+                    ..
+                } => queue_drop(app_handle, paths, *position),
+                RunEvent::Exit => {
+                    if let Some(cache) = &app_handle.state::<Linkdrop>().cache {
+                        cache.flush();
+                    }
+                }
+                _ => {}
+            })
+            .build()
+    }
+
+    /// The file extensions (lowercase, without the dot) of dropped files this
+    /// configuration handles, e.g. for a drop-zone hint. The frontend can ask
+    /// the running plugin the same through `plugin:linkdrop|supported_extensions`.
+    pub fn supported_extensions(&self) -> Vec<String> {
+        self.drop.extensions().into_iter().map(String::from).collect()
+    }
+
+    /// Handle drops with this configuration outside a running app, e.g. in
+    /// integration tests or a custom event loop; see [`DropHandler`]. There is
+    /// no app cache directory, so [`Builder::persist_cache`] keeps the cache
+    /// in memory only.
+    pub fn drop_handler(&self) -> Result<DropHandler, Box<dyn std::error::Error>> {
+        let cache = (self.persist_cache || self.cache_ttl.is_some())
+            .then(|| MetadataCache::new(self.cache_ttl.unwrap_or(DEFAULT_CACHE_TTL)));
+        Ok(DropHandler(self.state(cache)?))
+    }
+
+    fn state(&self, cache: Option<MetadataCache>) -> Result<Linkdrop, Box<dyn std::error::Error>> {
+        let mut fetch = self.fetch.clone();
+        fetch.extract.custom_selectors = self
+            .field_selectors
+            .iter()
+            .map(|(field, css, attribute)| CustomSelector::parse(*field, css, attribute.clone()))
+            .collect::<Result<_, _>>()?;
+        Ok(Linkdrop {
+            client: self.http_client()?,
+            fetch,
+            drop: self.drop.clone(),
+            should_fetch: self.should_fetch.clone(),
+            rewrite_url: self.rewrite_url.clone(),
+            map_error: self.map_error.clone().unwrap_or_else(|| Arc::new(default_error_payload)),
+            field_naming: self.field_naming,
+            delivery: self.delivery,
+            channels: Mutex::new(Vec::new()),
+            cache,
+            pool: self.worker_pool.map(WorkerPool::new).transpose()?,
+            seen: self.skip_seen_urls.then(|| SeenUrls::new(self.seen_urls_limit)),
+            pending_drop: Mutex::new(None),
+            next_batch_id: AtomicU64::new(1),
+            fetches: Mutex::new(HashMap::new()),
+            next_fetch_id: AtomicU64::new(1),
+            coalesce_window_ms: AtomicU64::new(self.coalesce_window.unwrap_or(DEFAULT_COALESCE_WINDOW).as_millis() as u64),
+        })
+    }
+
+    /// The request timeout and `User-Agent`: the ones set on this builder,
+    /// else those `env` gives for the `LINKDROP_*` variables, else the defaults.
+    fn client_settings(&self, env: impl Fn(&str) -> Option<String>) -> (Duration, String) {
+        let timeout = self
+            .timeout
+            .or_else(|| {
+                let ms = env("LINKDROP_TIMEOUT_MS")?;
+                ms.trim().parse().ok().map(Duration::from_millis)
+            })
+            .unwrap_or(DEFAULT_TIMEOUT);
+        let user_agent = self
+            .user_agent
+            .clone()
+            .or_else(|| env("LINKDROP_USER_AGENT").filter(|ua| !ua.trim().is_empty()))
+            .unwrap_or_else(|| DEFAULT_USER_AGENT.to_string());
+        (timeout, user_agent)
+    }
+
+    fn http_client(&self) -> Result<Client, Box<dyn std::error::Error>> {
+        let (timeout, user_agent) = self.client_settings(|name| std::env::var(name).ok());
+        let mut builder = Client::builder().timeout(timeout).user_agent(user_agent);
+        // reqwest counts the URL being redirected from among the redirects, so
+        // `limited(n)` only follows `n - 1` of them.
+        let max_redirects = self.max_redirects.unwrap_or(DEFAULT_MAX_REDIRECTS);
+        let redirects = Policy::limited(max_redirects.saturating_add(1));
+        let on_response = self.fetch.on_response.clone();
+        let filter = self.redirect_filter.clone();
+        builder = if on_response.is_none() && filter.is_none() {
+            builder.redirect(redirects)
+        } else {
+            builder.redirect(Policy::custom(move |attempt| {
+                let Some(from) = attempt.previous().last() else {
+                    return redirects.redirect(attempt);
+                };
+                if let Some(on_response) = &on_response {
+                    on_response(&ResponseAudit {
+                        url: from.to_string(),
+                        status: Some(attempt.status().as_u16()),
+                        bytes: 0,
+                        elapsed: None,
+                    });
+                }
+                if let Some(refused) = filter.as_ref().and_then(|f| refused_redirect(f, attempt.previous(), attempt.url())) {
+                    return attempt.error(refused);
+                }
+                redirects.redirect(attempt)
+            }))
+        };
+        // Advertise and decode compressed bodies so `extract` sees HTML, never
+        // raw gzip/brotli bytes.
+        #[cfg(feature = "compression")]
+        {
+            builder = builder.gzip(true).deflate(true).brotli(true);
+        }
+        #[cfg(feature = "cookies")]
+        if self.cookie_store {
+            let jar = Jar::default();
+            for (url, cookie) in &self.cookies {
+                jar.add_cookie_str(cookie, url);
             }
+            builder = builder.cookie_provider(Arc::new(jar));
+        }
+        for (host, addr) in &self.host_overrides {
+            builder = builder.resolve(host, *addr);
         }
+        if let Some(language) = &self.accept_language {
+            let mut headers = HeaderMap::new();
+            headers.insert(ACCEPT_LANGUAGE, HeaderValue::from_str(language)?);
+            builder = builder.default_headers(headers);
+        }
+        Ok(builder.build()?)
     }
+}
 
-    // Image
-    let image = document
-        .select(&scraper::Selector::parse(r#"meta[property=\"og:image\"]"#).unwrap())
-        .next()
-        .and_then(|m| m.value().attr("content"))
-        .map(|s| s.to_string());
+/// The chain so far, ending in `next`, when `filter` turns down the hop from
+/// the last of `previous` to `next`.
+fn refused_redirect(filter: &RedirectFilter, previous: &[Url], next: &Url) -> Option<RefusedRedirect> {
+    let from = previous.last()?;
+    if filter(from, next) {
+        return None;
+    }
+    Some(RefusedRedirect(previous.iter().chain([next]).map(Url::to_string).collect()))
+}
 
-    // Favicon
-    let favicon = document
-        .select(&scraper::Selector::parse(r#"link[rel~=\"icon\"]"#).unwrap())
-        .next()
-        .and_then(|l| l.value().attr("href"))
-        .map(|href| {
-            if href.starts_with("http") || href.starts_with("//") {
-                href.to_string()
-            } else {
-                base_url.join(href).map(|u| u.to_string()).unwrap_or_else(|_| href.to_string())
-            }
-        });
+/// The plugin's drop handling, detached from Tauri's event loop; built by
+/// [`Builder::drop_handler`]. Each call handles one dropped file with the same
+/// parsing, filtering, deduplication, cache and fetching as a drop on a
+/// window, but returns the result instead of emitting it. Only that one file
+/// is handled: a directory isn't expanded under [`Builder::directory_drops`],
+/// calls aren't coalesced into shared batches, and drops on windows don't go
+/// through a `DropHandler`.
+pub struct DropHandler(Linkdrop);
 
-    Ok(LinkMetadata {
-        url: url.to_string(),
-        title,
-        description,
-        image,
-        favicon,
-    })
-} 
\ No newline at end of file
+impl DropHandler {
+    /// The `link-dropped` payload for a drop of `path`, or `None` when the
+    /// plugin would emit no `link-dropped` for it: the file isn't a supported
+    /// shortcut, can't be read, is a duplicate under
+    /// [`Builder::skip_seen_urls`], lacks a [`Builder::require_fields`] field
+    /// or is turned down under [`Builder::suppress_unfetched`]. Each call is
+    /// its own batch of one, dropped at `(0, 0)`.
+    pub async fn handle_drop(&self, path: &Path) -> Option<LinkDropEvent> {
+        let state = &self.0;
+        let DropOutcome::Link(metadata, _) = handle_dropped_file(state, path, &SeenUrls::new(None)).await? else {
+            return None;
+        };
+        if state.drop.insufficient(&metadata).is_some() {
+            return None;
+        }
+        Some(LinkDropEvent {
+            version: LINK_DROP_EVENT_VERSION,
+            metadata: *metadata,
+            batch_id: state.next_batch_id.fetch_add(1, Ordering::Relaxed),
+            batch_total: 1,
+            path: path.to_path_buf(),
+            x: 0.0,
+            y: 0.0,
+            dropped_at: unix_millis(),
+        })
+    }
+}
+
+pub fn init<R: Runtime>() -> TauriPlugin<R> {
+    Builder::new().build()
+}
+
+/// Add `paths` to the drop gesture being coalesced, starting a new one if none
+/// is pending. The gesture is processed once [`Builder::coalesce_window`]
+/// elapses.
+fn queue_drop<R: Runtime>(app: &AppHandle<R>, paths: &[PathBuf], position: PhysicalPosition<f64>) {
+    let state = app.state::<Linkdrop>();
+    let mut pending = state.pending_drop.lock().unwrap();
+    let dropped = paths.iter().map(|path| (path.clone(), position));
+    if let Some(batch) = pending.as_mut() {
+        batch.extend(dropped);
+        return;
+    }
+    *pending = Some(dropped.collect());
+
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let window = app.state::<Linkdrop>().coalesce_window_ms.load(Ordering::Relaxed);
+        std::thread::sleep(Duration::from_millis(window));
+        let paths = app.state::<Linkdrop>().pending_drop.lock().unwrap().take();
+        process_batch(&app, paths.unwrap_or_default());
+    });
+}
+
+/// Fetch every shortcut in one drop gesture concurrently, emitting one
+/// `link-dropped` per distinct URL as it resolves. Files pointing at a URL an
+/// earlier file of the gesture already did, such as a `.webloc` dragged along
+/// with a `.txt` holding the same link, get a `link-duplicate` instead.
+///
+/// Tauri's drop event only carries file paths, so text or URLs dragged
+/// straight from a browser never reach the plugin; the frontend can pass
+/// those to `fetch_link` itself.
+fn process_batch<R: Runtime>(app: &AppHandle<R>, dropped: Vec<DroppedPath>) {
+    let state = app.state::<Linkdrop>();
+    let dropped = expand_directories(&state.drop, dropped);
+    let (links, unsupported): (Vec<_>, Vec<_>) = dropped.into_iter().partition(|(p, _)| state.drop.accepts(p));
+    if state.drop.report_unsupported {
+        for (path, _) in unsupported {
+            let extension = extension(&path);
+            state.emit(app, "link-drop-unsupported", UnsupportedDrop { path, extension });
+        }
+    }
+    if links.is_empty() {
+        return;
+    }
+    let batch_id = state.next_batch_id.fetch_add(1, Ordering::Relaxed);
+    let dropped_at = unix_millis();
+    let batch_total = links.len();
+    let gesture = Arc::new(SeenUrls::new(None));
+    for (path, position) in links {
+        let app = app.clone();
+        let gesture = gesture.clone();
+        let job = async move {
+            let state = app.state::<Linkdrop>();
+            match handle_dropped_file(&state, &path, &gesture).await {
+                Some(DropOutcome::Link(metadata, error)) => {
+                    if let Some(error) = error {
+                        let error = (state.map_error)(&error);
+                        let url = Some(metadata.url.clone());
+                        state.emit(&app, "link-drop-error", DropError { path: path.clone(), url, batch_id, error });
+                    }
+                    let event = match state.drop.insufficient(&metadata) {
+                        None => "link-dropped",
+                        Some(Insufficient::Report) => "link-drop-insufficient",
+                        Some(Insufficient::Suppress) => return,
+                    };
+                    state.emit(
+                        &app,
+                        event,
+                        LinkDropEvent {
+                            version: LINK_DROP_EVENT_VERSION,
+                            metadata: *metadata,
+                            batch_id,
+                            batch_total,
+                            path,
+                            x: position.x,
+                            y: position.y,
+                            dropped_at,
+                        },
+                    );
+                }
+                Some(DropOutcome::Duplicate(url)) => {
+                    state.emit(&app, "link-duplicate", DuplicateLink { url, batch_id });
+                }
+                Some(DropOutcome::Unreadable(error)) => {
+                    let error = (state.map_error)(&error);
+                    state.emit(&app, "link-drop-error", DropError { path, url: None, batch_id, error });
+                }
+                None => {}
+            }
+        };
+        state.run(job);
+    }
+}
+
+/// With [`Builder::directory_drops`], replace each dropped directory by the
+/// shortcut files found in it.
+fn expand_directories(config: &DropConfig, dropped: Vec<DroppedPath>) -> Vec<DroppedPath> {
+    let Some((max_depth, max_files)) = config.directories else {
+        return dropped;
+    };
+    let mut expanded = Vec::new();
+    for (path, position) in dropped {
+        if !path.is_dir() {
+            expanded.push((path, position));
+            continue;
+        }
+        let mut found = Vec::new();
+        let mut budget = MAX_DIRECTORY_ENTRIES;
+        walk_directory(config, &path, max_depth, max_files, &mut budget, &mut found);
+        expanded.extend(found.into_iter().map(|file| (file, position)));
+    }
+    expanded
+}
+
+/// Add the shortcut files under `dir` to `found`, until it holds `max_files`
+/// or `budget` entries have been looked at.
+fn walk_directory(
+    config: &DropConfig,
+    dir: &Path,
+    depth: usize,
+    max_files: usize,
+    budget: &mut usize,
+    found: &mut Vec<PathBuf>,
+) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    let mut entries: Vec<_> = entries.filter_map(Result::ok).take(*budget).collect();
+    *budget -= entries.len();
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        if found.len() >= max_files {
+            return;
+        }
+        // `file_type` doesn't follow symlinks, so linked folders are skipped.
+        let Ok(file_type) = entry.file_type() else { continue };
+        let path = entry.path();
+        if file_type.is_dir() && depth > 0 {
+            walk_directory(config, &path, depth - 1, max_files, budget, found);
+        } else if !file_type.is_dir() && config.accepts(&path) {
+            found.push(path);
+        }
+    }
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase)
+}
+
+/// `gesture` holds the URLs of the drop gesture's files handled so far.
+async fn handle_dropped_file(state: &Linkdrop, path: &Path, gesture: &SeenUrls) -> Option<DropOutcome> {
+    if !state.drop.accepts(path) {
+        return None;
+    }
+    // Invalid URLs still make a URL-only link, failing with their parse error.
+    let url = match read_shortcut_text(path, state.drop.max_shortcut_size) {
+        Ok(url) => url,
+        Err(e) => return Some(DropOutcome::Unreadable(e)),
+    };
+    let parsed = parse_link(&url);
+    let vetoed = parsed.as_ref().is_ok_and(|parsed| !state.should_fetch(parsed));
+    if vetoed && state.drop.suppress_unfetched {
+        return None;
+    }
+    let normalized = normalize_url(url.clone());
+    if !gesture.insert(&normalized) {
+        return Some(DropOutcome::Duplicate(url));
+    }
+    if let Some(seen) = &state.seen {
+        if !seen.insert(&normalized) {
+            return Some(DropOutcome::Duplicate(url));
+        }
+    }
+    let result = match parsed {
+        Ok(_) if vetoed => Ok(LinkMetadata {
+            url: url.clone(),
+            ..Default::default()
+        }),
+        Ok(parsed) => fetch_parsed(state, &url, parsed).await,
+        Err(e) => Err(e),
+    };
+    Some(match result {
+        Ok(meta) => DropOutcome::Link(Box::new(meta), None),
+        Err(e) => DropOutcome::Link(Box::new(LinkMetadata::fallback(url, &e)), Some(e)),
+    })
+}
+
+/// Read the URL out of a `.url` or `.webloc` shortcut file, or a `.txt` file
+/// whose first non-empty line is an http(s) URL, without fetching it. Files
+/// over 1 MiB are refused with [`LinkdropError::ParseFailed`].
+pub fn parse_shortcut(path: &Path) -> Result<String, LinkdropError> {
+    read_shortcut(path, DEFAULT_MAX_SHORTCUT_SIZE)
+}
+
+/// [`parse_shortcut`] with a size limit of `max_size` bytes.
+fn read_shortcut(path: &Path, max_size: u64) -> Result<String, LinkdropError> {
+    let url = read_shortcut_text(path, max_size)?;
+    Url::parse(&url).map_err(|e| LinkdropError::ParseFailed(format!("invalid URL `{url}`: {e}")))?;
+    Ok(url)
+}
+
+/// The cleaned-up URL text of a shortcut file, not yet checked to be a URL.
+fn read_shortcut_text(path: &Path, max_size: u64) -> Result<String, LinkdropError> {
+    let ext = extension(path).unwrap_or_default();
+    let file = fs::File::open(path)?;
+    let size = file.metadata()?.len();
+    if size > max_size {
+        return Err(LinkdropError::ParseFailed(format!(
+            "shortcut file is {size} bytes, over the {max_size} byte limit"
+        )));
+    }
+    let mut content = String::new();
+    // The file may still grow after the check; never read past the limit.
+    file.take(max_size).read_to_string(&mut content)?;
+    let raw = find_url(&ext, &content).ok_or_else(|| LinkdropError::ParseFailed("no URL found".into()))?;
+    // Some writers leave a trailing NUL or other control characters behind.
+    let url = raw.trim_matches(|c: char| c.is_whitespace() || c.is_control());
+    if url.is_empty() {
+        return Err(LinkdropError::ParseFailed("empty URL".into()));
+    }
+    Ok(url.to_string())
+}
+
+/// The raw URL text in a shortcut file's `content`, before any cleanup.
+fn find_url<'a>(ext: &str, content: &'a str) -> Option<&'a str> {
+    if ext == "url" {
+        // Prefer the `URL=` under `[InternetShortcut]`; other sections (or a
+        // file with no headers at all) are only used if it has none.
+        let mut section = None;
+        let mut fallback = None;
+        for line in content.lines().map(str::trim) {
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = Some(name);
+            } else if let Some(rest) = line.strip_prefix("URL=") {
+                if section.is_some_and(|name| name.eq_ignore_ascii_case("InternetShortcut")) {
+                    return Some(rest);
+                }
+                fallback = fallback.or(Some(rest));
+            }
+        }
+        fallback
+    } else if ext == "webloc" {
+        // crude XML/plist parsing for <string>URL</string>
+        let after = content.find("<string>")? + "<string>".len();
+        let end = content[after..].find("</string>")?;
+        Some(&content[after..after + end])
+    } else if ext == "txt" {
+        let line = content.lines().map(str::trim).find(|l| !l.is_empty())?;
+        let is_link = !line.contains(char::is_whitespace)
+            && Url::parse(line).is_ok_and(|u| matches!(u.scheme(), "http" | "https"));
+        is_link.then_some(line)
+    } else {
+        None
+    }
+}
+
+/// Check that a dropped link is an http(s) URL, apply the app's rewrite and
+/// fetch it. The returned metadata keeps the original `url`.
+async fn resolve_link(state: &Linkdrop, url: &str) -> Result<LinkMetadata, LinkdropError> {
+    let parsed = parse_link(url)?;
+    if !state.should_fetch(&parsed) {
+        return Ok(LinkMetadata {
+            url: url.to_string(),
+            ..Default::default()
+        });
+    }
+    fetch_parsed(state, url, parsed).await
+}
+
+/// [`resolve_link`] for a link already parsed and let through by
+/// [`Builder::should_fetch`].
+async fn fetch_parsed(state: &Linkdrop, url: &str, parsed: Url) -> Result<LinkMetadata, LinkdropError> {
+    let target = match &state.rewrite_url {
+        Some(rewrite) => rewrite(&parsed),
+        None => parsed,
+    };
+    let host = target.host_str().unwrap_or_default();
+    if !state.fetch.hosts.permits(host) {
+        return Err(LinkdropError::Blocked(host.to_string()));
+    }
+    // Cached under the link as given, so `clear_cache` can name it whatever
+    // the rewrite or a `www.` retry actually fetched.
+    let key = normalize_url(url.to_string());
+    let fetch = async {
+        let result = fetch_cached(state, &key, target.as_str()).await;
+        let unreachable = matches!(&result, Err(LinkdropError::Http(e)) if e.is_connect());
+        let alternate = toggle_www(&target)
+            .filter(|_| unreachable && state.fetch.www_fallback)
+            .filter(|alternate| state.fetch.hosts.permits(alternate.host_str().unwrap_or_default()));
+        match alternate {
+            Some(alternate) => fetch_cached(state, &key, alternate.as_str()).await.or(result),
+            None => result,
+        }
+    };
+    let deadline = state.fetch.deadline;
+    let mut meta = tokio::time::timeout(deadline, fetch)
+        .await
+        .map_err(|_| LinkdropError::Timeout(deadline))??;
+    meta.url = url.to_string();
+    Ok(meta)
+}
+
+/// `url` with a `www.` prefix added to its host, or removed if it has one; `None`
+/// for IP addresses and hosts that would be left empty.
+fn toggle_www(url: &Url) -> Option<Url> {
+    let host = url.host_str()?;
+    // IPv6 hosts come bracketed, e.g. `[::1]`.
+    if host.starts_with('[') || host.parse::<std::net::Ipv4Addr>().is_ok() {
+        return None;
+    }
+    let toggled = match host.strip_prefix("www.") {
+        Some(bare) if !bare.is_empty() => bare.to_string(),
+        Some(_) => return None,
+        None => format!("www.{host}"),
+    };
+    let mut alternate = url.clone();
+    alternate.set_host(Some(&toggled)).ok()?;
+    Some(alternate)
+}
+
+/// Parse `url`, refusing anything but http(s) URLs with a host, so links like
+/// `http://` fail here instead of as a confusing request error.
+fn parse_link(url: &str) -> Result<Url, LinkdropError> {
+    let parsed = Url::parse(url).map_err(|e| LinkdropError::ParseFailed(e.to_string()))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(LinkdropError::UnsupportedScheme(parsed.scheme().to_string()));
+    }
+    if parsed.host_str().is_none_or(str::is_empty) {
+        return Err(LinkdropError::ParseFailed(format!("`{url}` has no host")));
+    }
+    Ok(parsed)
+}
+
+/// Fetch `url` through the plugin's cache, when one is configured, storing the
+/// result under `key`.
+async fn fetch_cached(state: &Linkdrop, key: &str, url: &str) -> Result<LinkMetadata, LinkdropError> {
+    let Some(cache) = &state.cache else {
+        let resp = send(state.client.get(url), &state.fetch).await?;
+        return read_metadata(&state.client, url, resp, &state.fetch).await;
+    };
+    let cached = cache.get(key);
+    if let Some(entry) = &cached {
+        if cache.is_fresh(entry) {
+            return Ok(entry.metadata.clone());
+        }
+    }
+
+    let mut req = state.client.get(url);
+    if let Some(etag) = cached.as_ref().and_then(|e| e.etag.as_deref()) {
+        req = req.header(IF_NONE_MATCH, etag);
+    }
+    let resp = send(req, &state.fetch).await?;
+    if resp.status() == StatusCode::NOT_MODIFIED {
+        if let Some(entry) = cached {
+            audit(&resp, 0, &state.fetch);
+            cache.insert(key, entry.metadata.clone(), entry.etag);
+            return Ok(entry.metadata);
+        }
+    }
+
+    let etag = resp
+        .headers()
+        .get(ETAG)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let meta = read_metadata(&state.client, url, resp, &state.fetch).await?;
+    cache.insert(key, meta.clone(), etag);
+    Ok(meta)
+}
+
+/// Fetch `url` and extract its page metadata, giving up with
+/// [`LinkdropError::Timeout`] after 30 seconds.
+pub async fn fetch_metadata(client: &Client, url: &str) -> Result<LinkMetadata, LinkdropError> {
+    let config = FetchConfig::default();
+    let fetch = async { read_metadata(client, url, send(client.get(url), &config).await?, &config).await };
+    tokio::time::timeout(config.deadline, fetch)
+        .await
+        .map_err(|_| LinkdropError::Timeout(config.deadline))?
+}
+
+/// Read the URL out of the shortcut at `path` and fetch its metadata, for apps
+/// doing their own drop handling. Uses a client with the default timeout and
+/// user agent; see [`parse_shortcut`] and [`fetch_metadata`].
+pub async fn resolve_shortcut_and_fetch(path: &Path) -> Result<LinkMetadata, LinkdropError> {
+    let url = parse_shortcut(path)?;
+    let client = Client::builder()
+        .timeout(DEFAULT_TIMEOUT)
+        .user_agent(DEFAULT_USER_AGENT)
+        .build()?;
+    fetch_metadata(&client, &url).await
+}
+
+/// Fetch the first of `urls` that succeeds, trying them in order, e.g. a
+/// primary host followed by its mirrors. The metadata's `url` is the candidate
+/// it came from. Errors from earlier candidates are dropped; if every candidate
+/// fails, the last one's error is returned.
+pub async fn fetch_metadata_mirrored(client: &Client, urls: &[&str]) -> Result<LinkMetadata, LinkdropError> {
+    let mut last_error = LinkdropError::ParseFailed("no URLs given".into());
+    for url in urls {
+        match fetch_metadata(client, url).await {
+            Ok(metadata) => return Ok(metadata),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+/// Like [`fetch_metadata`], but first yields a [`MetadataUpdate::Partial`]
+/// carrying just the URL so callers can show a placeholder immediately. The
+/// partial update always comes first and is followed by exactly one
+/// [`MetadataUpdate::Complete`].
+pub fn fetch_metadata_stream(client: Client, url: String) -> impl Stream<Item = MetadataUpdate> {
+    let partial = MetadataUpdate::Partial(LinkMetadata {
+        url: url.clone(),
+        ..Default::default()
+    });
+    stream::once(async { partial }).chain(stream::once(async move {
+        MetadataUpdate::Complete(fetch_metadata(&client, &url).await)
+    }))
+}
+
+/// Send `req`, retrying `429` responses as configured by
+/// [`Builder::rate_limit_retries`]. A `404` is retried once more with a
+/// trailing slash added to the path, for servers that only answer on the
+/// directory-style URL; see [`slashed_path`] for which URLs qualify. This is
+/// separate from the rate limit retries.
+async fn send(req: RequestBuilder, config: &FetchConfig) -> Result<Response, LinkdropError> {
+    let retry = req.try_clone().expect("GET requests have no streaming body");
+    let resp = send_rate_limited(req, config).await?;
+    if resp.status() != StatusCode::NOT_FOUND {
+        return Ok(resp);
+    }
+    let (client, request) = retry.build_split();
+    let mut request = request?;
+    let Some(path) = slashed_path(request.url()) else {
+        return Ok(resp);
+    };
+    request.url_mut().set_path(&path);
+    let slashed = send_rate_limited(RequestBuilder::from_parts(client, request), config).await?;
+    if slashed.status().is_success() {
+        audit(&resp, 0, config);
+        Ok(slashed)
+    } else {
+        audit(&slashed, 0, config);
+        Ok(resp)
+    }
+}
+
+/// `url`'s path with a trailing slash added, when it looks like a directory
+/// missing one: no slash yet, no extension on the last segment and no query.
+fn slashed_path(url: &Url) -> Option<String> {
+    let path = url.path();
+    let last = path.rsplit('/').next().unwrap_or_default();
+    if last.is_empty() || last.contains('.') || url.query().is_some() {
+        return None;
+    }
+    Some(format!("{path}/"))
+}
+
+async fn send_rate_limited(req: RequestBuilder, config: &FetchConfig) -> Result<Response, LinkdropError> {
+    let mut attempt = 0;
+    loop {
+        let resp = execute(req.try_clone().expect("GET requests have no streaming body"), config).await?;
+        if resp.status() != StatusCode::TOO_MANY_REQUESTS {
+            return Ok(resp);
+        }
+        audit(&resp, 0, config);
+        let retry_after = retry_after(&resp);
+        let wait = retry_after.unwrap_or(DEFAULT_RETRY_DELAY);
+        if attempt >= config.rate_limit_retries || wait > config.max_retry_after {
+            return Err(LinkdropError::RateLimited { retry_after });
+        }
+        attempt += 1;
+        tokio::time::sleep(wait).await;
+    }
+}
+
+/// Send `req` once. A request that fails is reported to
+/// [`Builder::on_response`] here; a response is reported once its body has been
+/// read or discarded.
+async fn execute(req: RequestBuilder, config: &FetchConfig) -> reqwest::Result<Response> {
+    let sent = Instant::now();
+    let (client, request) = req.build_split();
+    let mut request = request?;
+    if let Some(credentials) = request.url().host_str().and_then(|host| config.credentials.get(host)) {
+        request = RequestBuilder::from_parts(client.clone(), request)
+            .basic_auth(&credentials.username, credentials.password.as_ref())
+            .build()?;
+    }
+    let url = request.url().clone();
+    match client.execute(request).await {
+        Ok(mut resp) => {
+            resp.extensions_mut().insert(SentAt(sent));
+            Ok(resp)
+        }
+        Err(e) => {
+            config.observe(&url, None, 0, Some(sent.elapsed()));
+            Err(e)
+        }
+    }
+}
+
+/// The delay a `Retry-After` header asks for, given as seconds or an HTTP date.
+fn retry_after(resp: &Response) -> Option<Duration> {
+    let value = resp.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = httpdate::parse_http_date(value).ok()?;
+    Some(at.duration_since(SystemTime::now()).unwrap_or_default())
+}
+
+async fn read_metadata(
+    client: &Client,
+    url: &str,
+    resp: Response,
+    config: &FetchConfig,
+) -> Result<LinkMetadata, LinkdropError> {
+    let base_url = resp.url().clone();
+    let final_url = base_url.to_string();
+    let html = read_body(resp, config.max_body_size, config.head_only, config).await?;
+    let page = extract(url, &base_url, &html, &config.extract);
+    let mut metadata = page.metadata;
+    let cross_origin_canonical = || {
+        let canonical = Url::parse(metadata.canonical_url.as_deref()?).ok()?;
+        let follow = config.follow_cross_origin_canonical
+            && canonical.origin() != base_url.origin()
+            && is_public_url(&canonical);
+        follow.then_some(canonical)
+    };
+    let amp_canonical = page.amp_canonical.filter(|canonical| config.unwrap_amp && may_follow(canonical, &base_url));
+    let canonical = amp_canonical.or_else(cross_origin_canonical);
+    if let Some(canonical) = canonical {
+        if let Some(article) = fetch_canonical(client, url, &canonical, config).await {
+            metadata = article;
+            metadata.canonical_url = Some(canonical.to_string());
+        }
+    }
+    if let Some(endpoint) = page.oembed.filter(|endpoint| config.oembed && may_follow(endpoint, &base_url)) {
+        if let Some(oembed) = fetch_json::<OEmbed>(client, endpoint, config).await {
+            let fields = config.extract.fields;
+            let mut supplied = Vec::new();
+            if metadata.title.is_none() && fields.contains(Fields::TITLE) && oembed.title.is_some() {
+                metadata.title = oembed.title;
+                supplied.push("title");
+            }
+            if let Some(thumbnail) = oembed.thumbnail_url.filter(|_| fields.contains(Fields::IMAGE)) {
+                if metadata.image.is_none() {
+                    metadata.image = Some(thumbnail.clone());
+                    supplied.push("image");
+                }
+                if !metadata.images.contains(&thumbnail) {
+                    metadata.images.push(thumbnail);
+                }
+            }
+            if oembed.author_name.is_some() {
+                metadata.author = oembed.author_name;
+                supplied.push("author");
+            }
+            if oembed.html.is_some() {
+                metadata.embed_html = oembed.html;
+                supplied.push("embed_html");
+            }
+            if config.extract.record_sources {
+                for field in supplied {
+                    metadata.sources.insert(field.to_string(), "oembed".to_string());
+                }
+            }
+        }
+    }
+    if let Some(manifest_url) = page.manifest.filter(|manifest| config.web_manifest && may_follow(manifest, &base_url)) {
+        if let Some(manifest) = fetch_json::<WebManifest>(client, manifest_url.clone(), config).await {
+            let fields = config.extract.fields;
+            if metadata.title.is_none() && fields.contains(Fields::TITLE) {
+                metadata.title = manifest.name.or(manifest.short_name);
+            }
+            metadata.theme_color = manifest.theme_color;
+            if fields.contains(Fields::FAVICON) {
+                for icon in manifest.icons {
+                    let Ok(url) = manifest_url.join(icon.src.trim()) else { continue };
+                    let url = normalize_url(url.into());
+                    if metadata.icons.iter().all(|i| i.url != url) {
+                        metadata.icons.push(IconCandidate {
+                            url,
+                            rel: "manifest".to_string(),
+                            sizes: icon.sizes,
+                            mime_type: icon.mime_type,
+                            dimensions: None,
+                        });
+                    }
+                }
+                if metadata.favicon.is_none() {
+                    metadata.favicon = metadata.icons.first().map(|i| i.url.clone());
+                    if metadata.favicon.is_some() && config.extract.record_sources {
+                        metadata.sources.insert("favicon".to_string(), "manifest.json".to_string());
+                    }
+                }
+            }
+        }
+    }
+    let check_favicon = config.favicon_fallback && config.media == MediaMode::Full;
+    if metadata.favicon.is_none() && check_favicon && config.extract.fields.contains(Fields::FAVICON) {
+        let base_url = metadata.canonical_url.as_deref().and_then(|c| Url::parse(c).ok()).unwrap_or_else(|| base_url.clone());
+        if let Some(favicon) = fallback_favicon(client, &base_url, config).await {
+            if config.extract.record_sources {
+                metadata.sources.insert("favicon".to_string(), "/favicon.ico".to_string());
+            }
+            metadata.icons.push(IconCandidate {
+                url: favicon.clone(),
+                rel: "icon".to_string(),
+                sizes: None,
+                mime_type: None,
+                dimensions: None,
+            });
+            metadata.favicon = Some(favicon);
+        }
+    }
+    #[cfg(feature = "icon-dimensions")]
+    if config.probe_icons && config.media == MediaMode::Full {
+        icons::probe_icons(client, &base_url, &mut metadata.icons, config).await;
+    }
+    if config.media == MediaMode::Omit {
+        metadata.image = None;
+        metadata.images.clear();
+        metadata.favicon = None;
+        metadata.icons.clear();
+        metadata.sources.retain(|field, _| field != "image" && field != "favicon");
+    }
+    let proxied_image = metadata.image.as_ref().filter(|image| image.starts_with("http://") || image.starts_with("https://"));
+    if let (Some((template, width)), Some(image)) = (&config.image_proxy, proxied_image) {
+        let proxied = proxy_image(template, *width, image);
+        metadata.original_image = metadata.image.replace(proxied);
+    }
+    metadata.final_url = Some(final_url);
+    Ok(metadata)
+}
+
+/// `template` with `{width}` and a percent-encoded `{url}` filled in.
+fn proxy_image(template: &str, width: u32, url: &str) -> String {
+    let encoded: String = url
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{b:02X}"),
+        })
+        .collect();
+    template.replace("{width}", &width.to_string()).replace("{url}", &encoded)
+}
+
+/// `/favicon.ico` on `base_url`'s origin, if a `HEAD` finds it or doesn't
+/// answer within `timeout`. Servers that reject `HEAD` with `405` are asked
+/// once more with a single-byte ranged `GET`.
+async fn fallback_favicon(client: &Client, base_url: &Url, config: &FetchConfig) -> Option<String> {
+    let favicon = base_url.join("/favicon.ico").ok()?;
+    let timeout = config.favicon_check_timeout;
+    let mut check = execute(client.head(favicon.clone()).timeout(timeout), config).await;
+    if let Ok(resp) = &check {
+        audit(resp, 0, config);
+    }
+    if matches!(&check, Ok(resp) if resp.status() == StatusCode::METHOD_NOT_ALLOWED) {
+        let get = client.get(favicon.clone()).header(RANGE, "bytes=0-0").timeout(timeout);
+        check = execute(get, config).await;
+        if let Ok(resp) = &check {
+            audit(resp, 0, config);
+        }
+    }
+    match check {
+        Ok(resp) if resp.status().is_success() => Some(favicon.into()),
+        Err(e) if e.is_timeout() => Some(favicon.into()),
+        _ => None,
+    }
+}
+
+/// Whether `url` is http(s) and not obviously aimed at this machine or its
+/// local network, for URLs taken from a page rather than the user.
+fn is_public_url(url: &Url) -> bool {
+    use std::net::IpAddr;
+    if parse_link(url.as_str()).is_err() {
+        return false;
+    }
+    let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+    // IPv6 hosts come bracketed, e.g. `[::1]`.
+    let Ok(ip) = host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() else {
+        return host != "localhost" && !host.ends_with(".localhost");
+    };
+    // `[::ffff:127.0.0.1]` reaches the same machine as `127.0.0.1`.
+    let ip = match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        IpAddr::V4(_) => ip,
+    };
+    match ip {
+        IpAddr::V4(ip) => !(ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified()),
+        IpAddr::V6(ip) => {
+            let first = ip.segments()[0];
+            let unique_local = first & 0xfe00 == 0xfc00;
+            let link_local = first & 0xffc0 == 0xfe80;
+            !(ip.is_loopback() || ip.is_unspecified() || unique_local || link_local)
+        }
+    }
+}
+
+/// Whether a follow-up request to `url`, which the page at `page` chose, may be
+/// sent: it has to be http(s), and public unless it's on the page's own host.
+fn may_follow(url: &Url, page: &Url) -> bool {
+    is_public_url(url) || (parse_link(url.as_str()).is_ok() && url.host_str() == page.host_str())
+}
+
+/// Fetch and extract a page's canonical article, without following any
+/// further canonical hops.
+async fn fetch_canonical(client: &Client, url: &str, canonical: &Url, config: &FetchConfig) -> Option<LinkMetadata> {
+    let resp = execute(client.get(canonical.clone()), config).await.ok()?;
+    if !resp.status().is_success() {
+        audit(&resp, 0, config);
+        return None;
+    }
+    let base_url = resp.url().clone();
+    let html = read_body(resp, config.max_body_size, config.head_only, config).await.ok()?;
+    Some(extract(url, &base_url, &html, &config.extract).metadata)
+}
+
+/// The fields of an oEmbed response that end up in [`LinkMetadata`].
+#[derive(Deserialize)]
+struct OEmbed {
+    title: Option<String>,
+    author_name: Option<String>,
+    thumbnail_url: Option<String>,
+    html: Option<String>,
+}
+
+/// The fields of a web app manifest that end up in [`LinkMetadata`].
+#[derive(Deserialize)]
+struct WebManifest {
+    name: Option<String>,
+    short_name: Option<String>,
+    theme_color: Option<String>,
+    #[serde(default)]
+    icons: Vec<ManifestIcon>,
+}
+
+#[derive(Deserialize)]
+struct ManifestIcon {
+    src: String,
+    sizes: Option<String>,
+    #[serde(rename = "type")]
+    mime_type: Option<String>,
+}
+
+/// Fetch and parse a follow-up JSON document such as an oEmbed response,
+/// within [`FOLLOW_UP_TIMEOUT`] and [`FOLLOW_UP_MAX_SIZE`].
+async fn fetch_json<T: serde::de::DeserializeOwned>(client: &Client, url: Url, config: &FetchConfig) -> Option<T> {
+    let resp = execute(client.get(url).timeout(FOLLOW_UP_TIMEOUT), config).await.ok()?;
+    if !resp.status().is_success() {
+        audit(&resp, 0, config);
+        return None;
+    }
+    let body = read_body(resp, FOLLOW_UP_MAX_SIZE, false, config).await.ok()?;
+    serde_json::from_str(&body).ok()
+}
+
+/// Read at most `limit` bytes of the body, refusing up front when the server
+/// says it will send more. With `head_only`, stop after `</head>` instead, and
+/// only refuse a large page once `limit` bytes arrive without one.
+async fn read_body(mut resp: Response, limit: u64, head_only: bool, config: &FetchConfig) -> Result<String, LinkdropError> {
+    let content_length = resp.content_length();
+    if let Some(length) = content_length.filter(|&length| length > limit && !head_only) {
+        audit(&resp, 0, config);
+        return Err(LinkdropError::TooLarge { limit, length });
+    }
+    let mut body = Vec::new();
+    let mut received = 0;
+    loop {
+        let chunk = match resp.chunk().await {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => break,
+            Err(e) => {
+                audit(&resp, body.len() as u64, config);
+                return Err(e.into());
+            }
+        };
+        received += chunk.len() as u64;
+        let room = (limit as usize).saturating_sub(body.len());
+        // Re-scan the tail of the previous chunk in case `</head>` straddles two.
+        let scan_from = body.len().saturating_sub(HEAD_CLOSE.len() - 1);
+        body.extend_from_slice(&chunk[..chunk.len().min(room)]);
+        if let Some(end) = head_only.then(|| head_end(&body, scan_from)).flatten() {
+            body.truncate(end);
+            break;
+        }
+        if body.len() as u64 >= limit {
+            if head_only && received > limit {
+                audit(&resp, body.len() as u64, config);
+                return Err(LinkdropError::TooLarge { limit, length: content_length.unwrap_or(received) });
+            }
+            break;
+        }
+    }
+    audit(&resp, body.len() as u64, config);
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+const HEAD_CLOSE: &[u8] = b"</head>";
+
+/// The offset just past the first `</head>` at or after `from`, in any case.
+fn head_end(body: &[u8], from: usize) -> Option<usize> {
+    body[from..]
+        .windows(HEAD_CLOSE.len())
+        .position(|window| window.eq_ignore_ascii_case(HEAD_CLOSE))
+        .map(|at| from + at + HEAD_CLOSE.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tauri::test::MockRuntime;
+    use tauri::Listener;
+    use wiremock::matchers::{header, header_regex, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// A `200` response serving `html`.
+    pub(crate) fn page(html: &str) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_raw(html.to_string(), "text/html")
+    }
+
+    /// Plugin state for `builder`, without a running app.
+    pub(crate) fn linkdrop(builder: Builder) -> Linkdrop {
+        builder.state(None).unwrap()
+    }
+
+    /// A mock app running the plugin as configured by `builder`.
+    pub(crate) fn mock_app(builder: Builder) -> tauri::App<MockRuntime> {
+        tauri::test::mock_builder()
+            .plugin(builder.build())
+            .build(tauri::test::mock_context(tauri::test::noop_assets()))
+            .unwrap()
+    }
+
+    /// Payloads of `event`, collected as `app` emits them.
+    pub(crate) fn record(app: &tauri::App<MockRuntime>, event: &str) -> Arc<Mutex<Vec<serde_json::Value>>> {
+        let payloads = Arc::new(Mutex::new(Vec::new()));
+        let sink = payloads.clone();
+        app.listen(event, move |event| {
+            sink.lock().unwrap().push(serde_json::from_str(event.payload()).unwrap());
+        });
+        payloads
+    }
+
+    /// Wait, for at most five seconds, until `payloads` holds `count` items.
+    pub(crate) async fn wait_for(payloads: &Mutex<Vec<serde_json::Value>>, count: usize) -> Vec<serde_json::Value> {
+        for _ in 0..500 {
+            if payloads.lock().unwrap().len() >= count {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let payloads = payloads.lock().unwrap().clone();
+        assert_eq!(payloads.len(), count, "{payloads:?}");
+        payloads
+    }
+
+    /// Answer every request on a local port with `head`, then `chunks` of body
+    /// one per `interval`, holding the connection open afterwards. For
+    /// responses wiremock can't produce. Returns the server's URL.
+    pub(crate) async fn raw_server(head: &'static str, chunks: &'static [&'static str], interval: Duration) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let _ = socket.read(&mut [0; 4096]).await;
+                    let _ = socket.write_all(head.as_bytes()).await;
+                    for chunk in chunks {
+                        tokio::time::sleep(interval).await;
+                        if socket.write_all(chunk.as_bytes()).await.is_err() {
+                            return;
+                        }
+                    }
+                    tokio::time::sleep(Duration::from_secs(60)).await;
+                });
+            }
+        });
+        format!("http://{addr}/")
+    }
+
+    /// Write a `.url` shortcut to `url` named `name` into `dir`.
+    pub(crate) fn shortcut(dir: &Path, name: &str, url: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, format!("[InternetShortcut]\nURL={url}\n")).unwrap();
+        path
+    }
+
+    #[cfg(feature = "cookies")]
+    #[tokio::test]
+    async fn cookie_store_sends_cookies_back() {
+        let server = MockServer::start().await;
+        Mock::given(path("/login"))
+            .respond_with(page("<title>Login</title>").insert_header("set-cookie", "session=abc; Path=/"))
+            .mount(&server)
+            .await;
+        Mock::given(path("/dashboard"))
+            .and(header("cookie", "session=abc"))
+            .respond_with(page("<title>Dashboard</title>"))
+            .mount(&server)
+            .await;
+        let dashboard = format!("{}/dashboard", server.uri());
+
+        let state = linkdrop(Builder::new().cookie_store(true));
+        resolve_link(&state, &format!("{}/login", server.uri())).await.unwrap();
+        let meta = resolve_link(&state, &dashboard).await.unwrap();
+        assert_eq!(meta.title.as_deref(), Some("Dashboard"));
+
+        let state = linkdrop(Builder::new());
+        resolve_link(&state, &format!("{}/login", server.uri())).await.unwrap();
+        assert_eq!(resolve_link(&state, &dashboard).await.unwrap().title, None);
+    }
+
+    #[cfg(feature = "cookies")]
+    #[tokio::test]
+    async fn seeded_cookie_is_sent() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(header("cookie", "session=seeded"))
+            .respond_with(page("<title>Dashboard</title>"))
+            .mount(&server)
+            .await;
+        let url = Url::parse(&server.uri()).unwrap();
+        let state = linkdrop(Builder::new().cookie(url.clone(), "session=seeded"));
+        let meta = resolve_link(&state, url.as_str()).await.unwrap();
+        assert_eq!(meta.title.as_deref(), Some("Dashboard"));
+    }
+
+    #[tokio::test]
+    async fn multi_file_drop_shares_one_batch() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET")).respond_with(page("<title>Page</title>")).mount(&server).await;
+        let dir = tempfile::tempdir().unwrap();
+        let paths: Vec<_> = ["a", "b", "c"]
+            .iter()
+            .map(|name| shortcut(dir.path(), &format!("{name}.url"), &format!("{}/{name}", server.uri())))
+            .collect();
+        let app = mock_app(Builder::new());
+        let dropped = record(&app, "link-dropped");
+
+        // One path per event, as some platforms deliver them.
+        for path in &paths {
+            queue_drop(app.handle(), std::slice::from_ref(path), PhysicalPosition::new(0.0, 0.0));
+        }
+        let events = wait_for(&dropped, 3).await;
+        let batch_id = &events[0]["batch_id"];
+        assert!(events.iter().all(|event| &event["batch_id"] == batch_id));
+        assert!(events.iter().all(|event| event["batch_total"] == 3));
+        let mut urls: Vec<_> = events.iter().map(|event| event["metadata"]["url"].as_str().unwrap()).collect();
+        urls.sort();
+        assert_eq!(urls, ["a", "b", "c"].map(|name| format!("{}/{name}", server.uri())));
+    }
+
+    #[tokio::test]
+    async fn oversized_content_length_is_refused_unread() {
+        let head = "HTTP/1.1 200 OK\r\ncontent-type: text/html\r\ncontent-length: 10000000000\r\n\r\n";
+        let url = raw_server(head, &[], Duration::ZERO).await;
+        let state = linkdrop(Builder::new());
+        let result = tokio::time::timeout(Duration::from_secs(2), resolve_link(&state, &url))
+            .await
+            .expect("the body should not be waited for");
+        assert!(matches!(
+            result,
+            Err(LinkdropError::TooLarge { limit: DEFAULT_MAX_BODY_SIZE, length: 10_000_000_000 })
+        ));
+    }
+
+    #[tokio::test]
+    async fn body_without_content_length_is_truncated() {
+        let head = "HTTP/1.1 200 OK\r\ncontent-type: text/html\r\n\r\n";
+        let url = raw_server(head, &["<title>Big</title>", "<p>and a lot more</p>"], Duration::ZERO).await;
+        let state = linkdrop(Builder::new().max_body_size(20).favicon_fallback(false));
+        let meta = resolve_link(&state, &url).await.unwrap();
+        assert_eq!(meta.title.as_deref(), Some("Big"));
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn gzip_bodies_are_decoded_before_parsing() {
+        // `<html><head><title>Compressed</title></head></html>`, gzipped.
+        const GZIPPED: &[u8] = b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x02\x03\xb3\xc9\x28\xc9\xcd\xb1\xb3\xc9\x48\x4d\x4c\xb1\xb3\x29\xc9\x2c\xc9\x49\xb5\x73\xce\xcf\x2d\x28\x4a\x2d\x2e\x4e\x4d\xb1\xd1\x87\x88\xd8\xe8\x43\xe4\xf5\xc1\x8a\x01\xac\xaf\x6f\x7e\x33\x00\x00\x00";
+        let server = MockServer::start().await;
+        Mock::given(path("/"))
+            .and(header_regex("accept-encoding", "gzip"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(GZIPPED, "text/html")
+                    .insert_header("content-encoding", "gzip"),
+            )
+            .mount(&server)
+            .await;
+        let state = linkdrop(Builder::new());
+        let meta = resolve_link(&state, &server.uri()).await.unwrap();
+        assert_eq!(meta.title.as_deref(), Some("Compressed"));
+    }
+
+    #[tokio::test]
+    async fn txt_files_holding_one_link_are_links() {
+        let server = MockServer::start().await;
+        Mock::given(path("/")).respond_with(page("<title>Linked</title>")).mount(&server).await;
+        let dir = tempfile::tempdir().unwrap();
+        let link = dir.path().join("link.txt");
+        fs::write(&link, format!("\n  {}\n", server.uri())).unwrap();
+        let prose = dir.path().join("notes.txt");
+        fs::write(&prose, format!("Remember to read {} later", server.uri())).unwrap();
+
+        let handler = Builder::new().txt_links(true).drop_handler().unwrap();
+        let event = handler.handle_drop(&link).await.unwrap();
+        assert_eq!(event.metadata.title.as_deref(), Some("Linked"));
+        assert!(handler.handle_drop(&prose).await.is_none());
+
+        let handler = Builder::new().drop_handler().unwrap();
+        assert!(handler.handle_drop(&link).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn drop_position_is_in_the_payload() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET")).respond_with(page("<title>Page</title>")).mount(&server).await;
+        let dir = tempfile::tempdir().unwrap();
+        let link = shortcut(dir.path(), "link.url", &server.uri());
+        let app = mock_app(Builder::new());
+        let dropped = record(&app, "link-dropped");
+
+        queue_drop(app.handle(), std::slice::from_ref(&link), PhysicalPosition::new(120.5, 48.0));
+        let event: LinkDropEvent = serde_json::from_value(wait_for(&dropped, 1).await.remove(0)).unwrap();
+        assert_eq!((event.x, event.y), (120.5, 48.0));
+        assert_eq!(event.path, link);
+    }
+
+    #[tokio::test]
+    async fn resolve_routes_a_host_to_the_given_address() {
+        let server = MockServer::start().await;
+        Mock::given(header("host", format!("docs.internal.test:{}", server.address().port()).as_str()))
+            .respond_with(page("<title>Internal docs</title>"))
+            .mount(&server)
+            .await;
+        let state = linkdrop(Builder::new().resolve("docs.internal.test", *server.address()));
+        let url = format!("http://docs.internal.test:{}/", server.address().port());
+        let meta = resolve_link(&state, &url).await.unwrap();
+        assert_eq!(meta.title.as_deref(), Some("Internal docs"));
+    }
+
+    /// A server titling its page after the requested host, and a builder that
+    /// sends `m.example.test` and `www.example.test` to it and rewrites links
+    /// from the former to the latter.
+    async fn two_host_server() -> (MockServer, Builder) {
+        let server = MockServer::start().await;
+        let port = server.address().port();
+        for host in ["m.example.test", "www.example.test"] {
+            Mock::given(header("host", format!("{host}:{port}").as_str()))
+                .respond_with(page(&format!("<title>{host}</title>")))
+                .mount(&server)
+                .await;
+        }
+        let builder = Builder::new()
+            .resolve("m.example.test", *server.address())
+            .resolve("www.example.test", *server.address())
+            .favicon_fallback(false)
+            .rewrite_url(|url| {
+                let mut url = url.clone();
+                if url.host_str() == Some("m.example.test") {
+                    url.set_host(Some("www.example.test")).unwrap();
+                }
+                url
+            });
+        (server, builder)
+    }
+
+    #[tokio::test]
+    async fn rewrite_url_changes_the_fetched_host() {
+        let (server, builder) = two_host_server().await;
+        let state = linkdrop(builder);
+        let url = format!("http://m.example.test:{}/story", server.address().port());
+        let meta = resolve_link(&state, &url).await.unwrap();
+        assert_eq!(meta.title.as_deref(), Some("www.example.test"));
+        assert_eq!(meta.url, url);
+    }
+
+    #[tokio::test]
+    async fn clear_cache_evicts_a_rewritten_link() {
+        let (server, builder) = two_host_server().await;
+        let app = mock_app(builder.cache_ttl(Duration::from_secs(60)));
+        let state = app.state::<Linkdrop>();
+        let url = format!("http://m.example.test:{}/story", server.address().port());
+
+        resolve_link(&state, &url).await.unwrap();
+        resolve_link(&state, &url).await.unwrap();
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+
+        let spelled = format!("HTTP://M.Example.test:{}/story#comments", server.address().port());
+        commands::clear_cache(app.state(), Some(spelled));
+        resolve_link(&state, &url).await.unwrap();
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+    }
+
+    /// Serve an AMP page at `/amp` whose canonical is `canonical`, and the
+    /// article at `/article`.
+    async fn mount_amp(server: &MockServer, canonical: &str) {
+        let amp = format!(r#"<html amp><head><title>AMP</title><link rel="canonical" href="{canonical}"></head></html>"#);
+        Mock::given(path("/amp")).respond_with(page(&amp)).mount(server).await;
+        Mock::given(path("/article")).respond_with(page("<title>Full article</title>")).mount(server).await;
+    }
+
+    #[tokio::test]
+    async fn unwrap_amp_uses_the_canonical_article() {
+        let server = MockServer::start().await;
+        mount_amp(&server, "/article").await;
+        let url = format!("{}/amp", server.uri());
+        let state = linkdrop(Builder::new().unwrap_amp(true));
+        let meta = resolve_link(&state, &url).await.unwrap();
+        assert_eq!(meta.title.as_deref(), Some("Full article"));
+        assert_eq!(meta.canonical_url, Some(format!("{}/article", server.uri())));
+        assert_eq!(meta.url, url);
+
+        let state = linkdrop(Builder::new());
+        assert_eq!(resolve_link(&state, &url).await.unwrap().title.as_deref(), Some("AMP"));
+    }
+
+    #[tokio::test]
+    async fn unwrap_amp_refuses_a_local_canonical_on_another_host() {
+        let server = MockServer::start().await;
+        mount_amp(&server, &format!("{}/article", server.uri())).await;
+        let state = linkdrop(Builder::new().unwrap_amp(true).resolve("amp.example.test", *server.address()));
+        let url = format!("http://amp.example.test:{}/amp", server.address().port());
+        let meta = resolve_link(&state, &url).await.unwrap();
+        assert_eq!(meta.title.as_deref(), Some("AMP"));
+    }
+
+    #[tokio::test]
+    async fn seen_urls_are_only_processed_once() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET")).respond_with(page("<title>Page</title>")).mount(&server).await;
+        let dir = tempfile::tempdir().unwrap();
+        let first = shortcut(dir.path(), "first.url", &format!("{}/page", server.uri()));
+        let again = shortcut(dir.path(), "again.url", &format!("{}/page#top", server.uri()));
+        let app = mock_app(Builder::new().skip_seen_urls(true).coalesce_window(Duration::from_millis(10)));
+        let dropped = record(&app, "link-dropped");
+        let duplicates = record(&app, "link-duplicate");
+        let origin = PhysicalPosition::new(0.0, 0.0);
+
+        queue_drop(app.handle(), std::slice::from_ref(&first), origin);
+        wait_for(&dropped, 1).await;
+        queue_drop(app.handle(), std::slice::from_ref(&again), origin);
+        let duplicate = wait_for(&duplicates, 1).await.remove(0);
+        assert_eq!(duplicate["url"], format!("{}/page#top", server.uri()));
+        assert_eq!(dropped.lock().unwrap().len(), 1);
+
+        commands::reset_seen_urls(app.state());
+        queue_drop(app.handle(), std::slice::from_ref(&again), origin);
+        wait_for(&dropped, 2).await;
+    }
+
+    #[tokio::test]
+    async fn rate_limited_fetch_waits_for_retry_after() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "1"))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET")).respond_with(page("<title>Eventually</title>")).mount(&server).await;
+        let state = linkdrop(Builder::new().rate_limit_retries(1));
+        let started = Instant::now();
+        let meta = resolve_link(&state, &server.uri()).await.unwrap();
+        assert_eq!(meta.title.as_deref(), Some("Eventually"));
+        assert!(started.elapsed() >= Duration::from_secs(1));
+        assert!(!meta.rate_limited);
+    }
+
+    #[tokio::test]
+    async fn exhausted_rate_limit_retries_flag_the_link() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "0"))
+            .mount(&server)
+            .await;
+        let state = linkdrop(Builder::new().rate_limit_retries(2));
+        let error = resolve_link(&state, &server.uri()).await.unwrap_err();
+        assert!(matches!(error, LinkdropError::RateLimited { retry_after: Some(wait) } if wait.is_zero()));
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+        assert!(LinkMetadata::fallback(server.uri(), &error).rate_limited);
+    }
+
+    #[tokio::test]
+    async fn retry_after_beyond_the_maximum_is_not_waited_for() {
+        let server = MockServer::start().await;
+        let in_an_hour = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(3600));
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(429).insert_header("retry-after", in_an_hour.as_str()))
+            .mount(&server)
+            .await;
+        let state = linkdrop(Builder::new().rate_limit_retries(3));
+        let error = resolve_link(&state, &server.uri()).await.unwrap_err();
+        let LinkdropError::RateLimited { retry_after: Some(wait) } = error else { panic!("{error:?}") };
+        assert!(wait > Duration::from_secs(3500));
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn slow_favicon_check_does_not_hold_up_the_page() {
+        let server = MockServer::start().await;
+        Mock::given(path("/")).respond_with(page("<title>No icon</title>")).mount(&server).await;
+        Mock::given(method("HEAD"))
+            .and(path("/favicon.ico"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+            .mount(&server)
+            .await;
+        let state = linkdrop(Builder::new().favicon_check_timeout(Duration::from_millis(200)));
+        let started = Instant::now();
+        let meta = resolve_link(&state, &server.uri()).await.unwrap();
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(meta.title.as_deref(), Some("No icon"));
+        assert_eq!(meta.favicon, Some(format!("{}/favicon.ico", server.uri())));
+    }
+
+    #[tokio::test]
+    async fn missing_favicon_is_not_reported() {
+        let server = MockServer::start().await;
+        Mock::given(path("/")).respond_with(page("<title>No icon</title>")).mount(&server).await;
+        let state = linkdrop(Builder::new());
+        assert_eq!(resolve_link(&state, &server.uri()).await.unwrap().favicon, None);
+    }
+
+    #[tokio::test]
+    async fn unsupported_drops_are_reported_when_asked() {
+        let dir = tempfile::tempdir().unwrap();
+        let pdf = dir.path().join("Report.PDF");
+        fs::write(&pdf, "%PDF-1.7").unwrap();
+        let app = mock_app(Builder::new().report_unsupported(true).coalesce_window(Duration::from_millis(10)));
+        let unsupported = record(&app, "link-drop-unsupported");
+        queue_drop(app.handle(), std::slice::from_ref(&pdf), PhysicalPosition::new(0.0, 0.0));
+        let event = wait_for(&unsupported, 1).await.remove(0);
+        assert_eq!(event["extension"], "pdf");
+        assert_eq!(event["path"], pdf.to_str().unwrap());
+
+        let app = mock_app(Builder::new().coalesce_window(Duration::from_millis(10)));
+        let unsupported = record(&app, "link-drop-unsupported");
+        queue_drop(app.handle(), std::slice::from_ref(&pdf), PhysicalPosition::new(0.0, 0.0));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(unsupported.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn link_drop_event_serializes_metadata_inside_the_envelope() {
+        let event = LinkDropEvent {
+            version: LINK_DROP_EVENT_VERSION,
+            metadata: LinkMetadata {
+                url: "https://example.com/".to_string(),
+                title: Some("Example".to_string()),
+                ..Default::default()
+            },
+            batch_id: 7,
+            batch_total: 2,
+            path: PathBuf::from("/tmp/example.url"),
+            x: 1.5,
+            y: 2.0,
+            dropped_at: 1_700_000_000_000,
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["version"], 1);
+        assert_eq!(json["metadata"]["url"], "https://example.com/");
+        assert_eq!(json["metadata"]["title"], "Example");
+        assert_eq!(json["batch_id"], 7);
+        assert_eq!(json["batch_total"], 2);
+        assert_eq!(json["path"], "/tmp/example.url");
+        assert_eq!((json["x"].as_f64(), json["y"].as_f64()), (Some(1.5), Some(2.0)));
+        assert_eq!(json["dropped_at"], 1_700_000_000_000u64);
+        assert!(json.get("url").is_none());
+
+        let back: LinkDropEvent = serde_json::from_value(json).unwrap();
+        assert_eq!(back.metadata.title.as_deref(), Some("Example"));
+    }
+
+    #[test]
+    fn client_settings_fall_back_to_the_environment() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| vars.iter().find(|(key, _)| *key == name).map(|(_, value)| value.to_string())
+        };
+        let defaults = (DEFAULT_TIMEOUT, DEFAULT_USER_AGENT.to_string());
+        assert_eq!(Builder::new().client_settings(env(&[])), defaults);
+
+        let vars = env(&[("LINKDROP_TIMEOUT_MS", " 2500 "), ("LINKDROP_USER_AGENT", "from-env/1.0")]);
+        let settings = Builder::new().client_settings(vars);
+        assert_eq!(settings, (Duration::from_millis(2500), "from-env/1.0".to_string()));
+        let builder = Builder::new().timeout(Duration::from_secs(3)).user_agent("from-builder/2.0");
+        assert_eq!(builder.client_settings(vars), (Duration::from_secs(3), "from-builder/2.0".to_string()));
+
+        let invalid = env(&[("LINKDROP_TIMEOUT_MS", "soon"), ("LINKDROP_USER_AGENT", "  ")]);
+        assert_eq!(Builder::new().client_settings(invalid), defaults);
+    }
+
+    #[tokio::test]
+    async fn user_agent_is_sent() {
+        let server = MockServer::start().await;
+        Mock::given(header("user-agent", "from-builder/2.0"))
+            .respond_with(page("<title>Matched</title>"))
+            .mount(&server)
+            .await;
+        let state = linkdrop(Builder::new().user_agent("from-builder/2.0"));
+        let meta = resolve_link(&state, &server.uri()).await.unwrap();
+        assert_eq!(meta.title.as_deref(), Some("Matched"));
+    }
+
+    #[tokio::test]
+    async fn not_found_is_retried_with_a_trailing_slash() {
+        let server = MockServer::start().await;
+        Mock::given(path("/docs/")).respond_with(page("<title>Docs</title>")).mount(&server).await;
+        let state = linkdrop(Builder::new());
+        let meta = resolve_link(&state, &format!("{}/docs", server.uri())).await.unwrap();
+        assert_eq!(meta.title.as_deref(), Some("Docs"));
+        assert_eq!(meta.final_url, Some(format!("{}/docs/", server.uri())));
+    }
+
+    #[tokio::test]
+    async fn not_found_files_and_queries_are_not_retried() {
+        let server = MockServer::start().await;
+        Mock::given(path("/report.pdf/")).respond_with(page("<title>Wrong</title>")).mount(&server).await;
+        Mock::given(path("/search/")).respond_with(page("<title>Wrong</title>")).mount(&server).await;
+        let state = linkdrop(Builder::new());
+        for link in ["/report.pdf", "/search?q=rust"] {
+            let meta = resolve_link(&state, &format!("{}{link}", server.uri())).await.unwrap();
+            assert_eq!(meta.title, None, "{link}");
+        }
+        let requests = server.received_requests().await.unwrap();
+        assert!(requests.iter().all(|request| !request.url.path().ends_with('/')));
+
+        let slashed = |url: &str| slashed_path(&Url::parse(url).unwrap());
+        assert_eq!(slashed("https://example.com/docs").as_deref(), Some("/docs/"));
+        assert_eq!(slashed("https://example.com/a.b/docs").as_deref(), Some("/a.b/docs/"));
+        assert_eq!(slashed("https://example.com/docs/"), None);
+        assert_eq!(slashed("https://example.com/"), None);
+        assert_eq!(slashed("https://example.com/index.html"), None);
+        assert_eq!(slashed("https://example.com/docs?page=2"), None);
+    }
+
+    #[tokio::test]
+    async fn favicon_check_falls_back_to_get_when_head_is_rejected() {
+        let server = MockServer::start().await;
+        Mock::given(path("/")).respond_with(page("<title>No icon</title>")).mount(&server).await;
+        Mock::given(method("HEAD"))
+            .and(path("/favicon.ico"))
+            .respond_with(ResponseTemplate::new(405))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/favicon.ico"))
+            .and(header("range", "bytes=0-0"))
+            .respond_with(ResponseTemplate::new(206))
+            .mount(&server)
+            .await;
+        let state = linkdrop(Builder::new());
+        let meta = resolve_link(&state, &server.uri()).await.unwrap();
+        assert_eq!(meta.favicon, Some(format!("{}/favicon.ico", server.uri())));
+    }
+
+    #[tokio::test]
+    async fn resolve_shortcut_and_fetch_reads_then_fetches() {
+        let server = MockServer::start().await;
+        Mock::given(path("/saved")).respond_with(page("<title>Saved page</title>")).mount(&server).await;
+        let dir = tempfile::tempdir().unwrap();
+        let webloc = dir.path().join("Saved.webloc");
+        let plist = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<plist version="1.0"><dict><key>URL</key><string>{}/saved</string></dict></plist>"#,
+            server.uri()
+        );
+        fs::write(&webloc, plist).unwrap();
+        let meta = resolve_shortcut_and_fetch(&webloc).await.unwrap();
+        assert_eq!(meta.url, format!("{}/saved", server.uri()));
+        assert_eq!(meta.title.as_deref(), Some("Saved page"));
+    }
+
+    /// A `302` to `location`.
+    fn redirect(location: &str) -> ResponseTemplate {
+        ResponseTemplate::new(302).insert_header("location", location)
+    }
+
+    #[tokio::test]
+    async fn redirect_chain_stops_at_the_limit() {
+        let server = MockServer::start().await;
+        for hop in 0..10 {
+            Mock::given(path(format!("/r{hop}"))).respond_with(redirect(&format!("/r{}", hop + 1))).mount(&server).await;
+        }
+        let state = linkdrop(Builder::new().max_redirects(3));
+        let error = resolve_link(&state, &format!("{}/r0", server.uri())).await.unwrap_err();
+        assert!(matches!(error, LinkdropError::TooManyRedirects(_)), "{error:?}");
+        assert_eq!(server.received_requests().await.unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn default_limit_follows_ten_redirects() {
+        let server = MockServer::start().await;
+        for hop in 0..11 {
+            Mock::given(path(format!("/r{hop}"))).respond_with(redirect(&format!("/r{}", hop + 1))).mount(&server).await;
+        }
+        Mock::given(path("/r11")).respond_with(page("<title>Arrived</title>")).mount(&server).await;
+        let state = linkdrop(Builder::new().fields(Fields::TITLE));
+        let meta = resolve_link(&state, &format!("{}/r1", server.uri())).await.unwrap();
+        assert_eq!(meta.title.as_deref(), Some("Arrived"));
+        let error = resolve_link(&state, &format!("{}/r0", server.uri())).await.unwrap_err();
+        assert!(matches!(error, LinkdropError::TooManyRedirects(_)), "{error:?}");
+    }
+
+    #[tokio::test]
+    async fn unselected_favicon_skips_the_favicon_check() {
+        let server = MockServer::start().await;
+        Mock::given(path("/")).respond_with(page("<title>No icon</title>")).mount(&server).await;
+        let state = linkdrop(Builder::new().fields(Fields::TITLE));
+        resolve_link(&state, &server.uri()).await.unwrap();
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn oembed_fills_in_author_and_embed() {
+        let server = MockServer::start().await;
+        let html = format!(
+            r#"<head><link rel="alternate" type="application/json+oembed" href="/oembed?url={0}/video"></head>"#,
+            server.uri()
+        );
+        Mock::given(path("/video")).respond_with(page(&html)).mount(&server).await;
+        let oembed = serde_json::json!({
+            "title": "A video",
+            "author_name": "Someone",
+            "thumbnail_url": "https://cdn.example.com/thumb.jpg",
+            "html": "<iframe src=\"https://example.com/embed\"></iframe>",
+        });
+        Mock::given(path("/oembed")).respond_with(ResponseTemplate::new(200).set_body_json(oembed)).mount(&server).await;
+
+        let state = linkdrop(Builder::new().oembed(true));
+        let meta = resolve_link(&state, &format!("{}/video", server.uri())).await.unwrap();
+        assert_eq!(meta.title.as_deref(), Some("A video"));
+        assert_eq!(meta.author.as_deref(), Some("Someone"));
+        assert_eq!(meta.image.as_deref(), Some("https://cdn.example.com/thumb.jpg"));
+        assert_eq!(meta.embed_html.as_deref(), Some(r#"<iframe src="https://example.com/embed"></iframe>"#));
+        assert!(meta.sources.is_empty());
+
+        let state = linkdrop(Builder::new().oembed(true).record_sources(true));
+        let meta = resolve_link(&state, &format!("{}/video", server.uri())).await.unwrap();
+        for field in ["title", "image", "author", "embed_html"] {
+            assert_eq!(meta.sources.get(field).map(String::as_str), Some("oembed"), "{field}");
+        }
+
+        let state = linkdrop(Builder::new().oembed(true).fields(Fields::DESCRIPTION));
+        let meta = resolve_link(&state, &format!("{}/video", server.uri())).await.unwrap();
+        assert_eq!((meta.title, meta.image), (None, None));
+        assert!(meta.images.is_empty());
+        assert_eq!(meta.author.as_deref(), Some("Someone"));
+    }
+
+    #[tokio::test]
+    async fn oembed_endpoint_on_a_local_address_is_not_fetched() {
+        let server = MockServer::start().await;
+        let html = format!(r#"<link rel="alternate" type="application/json+oembed" href="{}/oembed">"#, server.uri());
+        Mock::given(path("/video")).respond_with(page(&html)).mount(&server).await;
+        let state = linkdrop(Builder::new().oembed(true).resolve("video.example.test", *server.address()));
+        let url = format!("http://video.example.test:{}/video", server.address().port());
+        let meta = resolve_link(&state, &url).await.unwrap();
+        assert_eq!(meta.author, None);
+        let requests = server.received_requests().await.unwrap();
+        assert!(requests.iter().all(|request| request.url.path() != "/oembed"));
+    }
+
+    #[tokio::test]
+    async fn web_manifest_fills_in_name_theme_and_icons() {
+        let server = MockServer::start().await;
+        Mock::given(path("/app")).respond_with(page(r#"<link rel="manifest" href="/app.webmanifest">"#)).mount(&server).await;
+        let manifest = serde_json::json!({
+            "name": "The App",
+            "theme_color": "#123456",
+            "icons": [{ "src": "icons/192.png", "sizes": "192x192", "type": "image/png" }],
+        });
+        Mock::given(path("/app.webmanifest"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(manifest))
+            .mount(&server)
+            .await;
+
+        let state = linkdrop(Builder::new().web_manifest(true).favicon_fallback(false));
+        let meta = resolve_link(&state, &format!("{}/app", server.uri())).await.unwrap();
+        assert_eq!(meta.title.as_deref(), Some("The App"));
+        assert_eq!(meta.theme_color.as_deref(), Some("#123456"));
+        let icon = format!("{}/icons/192.png", server.uri());
+        assert_eq!(meta.favicon.as_deref(), Some(icon.as_str()));
+        assert_eq!(meta.icons[0].rel, "manifest");
+        assert_eq!(meta.icons[0].sizes.as_deref(), Some("192x192"));
+    }
+
+    #[tokio::test]
+    async fn web_manifest_on_a_local_address_is_not_fetched() {
+        let server = MockServer::start().await;
+        let html = format!(r#"<link rel="manifest" href="{}/app.webmanifest">"#, server.uri());
+        Mock::given(path("/app")).respond_with(page(&html)).mount(&server).await;
+        let state = linkdrop(Builder::new().web_manifest(true).resolve("app.example.test", *server.address()));
+        let url = format!("http://app.example.test:{}/app", server.address().port());
+        let meta = resolve_link(&state, &url).await.unwrap();
+        assert_eq!(meta.theme_color, None);
+        let requests = server.received_requests().await.unwrap();
+        assert!(requests.iter().all(|request| request.url.path() != "/app.webmanifest"));
+    }
+
+    #[test]
+    fn local_addresses_are_not_public() {
+        let public = |url: &str| is_public_url(&Url::parse(url).unwrap());
+        assert!(public("https://example.com/"));
+        assert!(public("http://93.184.216.34/"));
+        assert!(public("http://[2606:2800:220:1::]/"));
+        for local in [
+            "http://localhost/",
+            "http://app.localhost/",
+            "http://127.0.0.1/",
+            "http://10.0.0.1/",
+            "http://169.254.169.254/",
+            "http://[::1]/",
+            "http://[fd00::1]/",
+            "http://[::ffff:127.0.0.1]/",
+            "http://[::ffff:192.168.1.1]/",
+            "file:///etc/passwd",
+        ] {
+            assert!(!public(local), "{local}");
+        }
+    }
+
+    #[tokio::test]
+    async fn cross_origin_canonical_is_followed_when_enabled() {
+        let server = MockServer::start().await;
+        let port = server.address().port();
+        let original = format!("http://syndicated.example:{port}/original");
+        let story = format!(r#"<title>Syndicated copy</title><link rel="canonical" href="{original}">"#);
+        Mock::given(path("/story")).respond_with(page(&story)).mount(&server).await;
+        Mock::given(path("/original")).respond_with(page("<title>Original</title>")).mount(&server).await;
+        let url = format!("{}/story", server.uri());
+
+        let builder = Builder::new().resolve("syndicated.example", *server.address());
+        let state = linkdrop(builder.follow_cross_origin_canonical(true));
+        let meta = resolve_link(&state, &url).await.unwrap();
+        assert_eq!(meta.title.as_deref(), Some("Original"));
+        assert_eq!(meta.url, url);
+        assert_eq!(meta.canonical_url, Some(original.clone()));
+
+        let state = linkdrop(Builder::new().resolve("syndicated.example", *server.address()));
+        let meta = resolve_link(&state, &url).await.unwrap();
+        assert_eq!(meta.title.as_deref(), Some("Syndicated copy"));
+    }
+
+    #[tokio::test]
+    async fn cross_origin_canonical_on_a_mapped_loopback_is_not_followed() {
+        let server = MockServer::start().await;
+        let canonical = format!("http://[::ffff:127.0.0.1]:{}/original", server.address().port());
+        let story = format!(r#"<title>Syndicated copy</title><link rel="canonical" href="{canonical}">"#);
+        Mock::given(path("/story")).respond_with(page(&story)).mount(&server).await;
+        let state = linkdrop(Builder::new().follow_cross_origin_canonical(true));
+        let meta = resolve_link(&state, &format!("{}/story", server.uri())).await.unwrap();
+        assert_eq!(meta.title.as_deref(), Some("Syndicated copy"));
+        let requests = server.received_requests().await.unwrap();
+        assert!(requests.iter().all(|request| request.url.path() != "/original"));
+    }
+
+    #[test]
+    fn host_policy_allows_denies_and_matches_wildcards() {
+        let policy = |builder: Builder| builder.fetch.hosts;
+        let open = policy(Builder::new());
+        assert!(open.permits("anything.example"));
+
+        let allowed = policy(Builder::new().allow_host("docs.example.com").allow_host("*.corp.example"));
+        assert!(allowed.permits("docs.example.com"));
+        assert!(allowed.permits("DOCS.example.com"));
+        assert!(!allowed.permits("www.example.com"));
+        assert!(allowed.permits("wiki.corp.example"));
+        assert!(allowed.permits("a.b.corp.example"));
+        assert!(!allowed.permits("corp.example"));
+        assert!(!allowed.permits("evilcorp.example"));
+
+        let denied = policy(Builder::new().allow_host("*.corp.example").deny_host("secret.corp.example"));
+        assert!(denied.permits("wiki.corp.example"));
+        assert!(!denied.permits("secret.corp.example"));
+        assert!(!policy(Builder::new().deny_host("*.ads.example")).permits("x.ads.example"));
+    }
+
+    #[tokio::test]
+    async fn blocked_host_is_never_fetched() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET")).respond_with(page("<title>Page</title>")).mount(&server).await;
+        let state = linkdrop(Builder::new().deny_host("127.0.0.1"));
+        let result = resolve_link(&state, &format!("{}/page", server.uri())).await;
+        assert!(matches!(result, Err(LinkdropError::Blocked(host)) if host == "127.0.0.1"));
+        assert!(server.received_requests().await.unwrap().is_empty());
+
+        let state = linkdrop(Builder::new().allow_host("127.0.0.1"));
+        assert!(resolve_link(&state, &format!("{}/page", server.uri())).await.is_ok());
+    }
+
+    #[test]
+    fn supported_extensions_follow_the_configuration() {
+        assert_eq!(Builder::new().supported_extensions(), ["url", "webloc"]);
+        assert_eq!(Builder::new().txt_links(true).supported_extensions(), ["url", "webloc", "txt"]);
+
+        let app = mock_app(Builder::new().txt_links(true));
+        assert_eq!(commands::supported_extensions(app.state()), ["url", "webloc", "txt"]);
+    }
+
+    #[test]
+    fn delivery_reaches_the_selected_mechanism() {
+        for delivery in [Delivery::Event, Delivery::Channel, Delivery::Both] {
+            let app = mock_app(Builder::new().delivery(delivery));
+            let events = record(&app, "link-duplicate");
+            let messages = Arc::new(Mutex::new(Vec::new()));
+            let sink = messages.clone();
+            let channel = Channel::new(move |body| {
+                if let tauri::ipc::InvokeResponseBody::Json(json) = body {
+                    sink.lock().unwrap().push(serde_json::from_str::<serde_json::Value>(&json).unwrap());
+                }
+                Ok(())
+            });
+            let state = app.state::<Linkdrop>();
+            state.channels.lock().unwrap().push(channel);
+
+            state.emit(app.handle(), "link-duplicate", DuplicateLink { url: "https://example.com/".to_string(), batch_id: 7 });
+            let payload = serde_json::json!({ "url": "https://example.com/", "batch_id": 7 });
+            let expected_events = if delivery == Delivery::Channel { vec![] } else { vec![payload.clone()] };
+            let expected_messages = if delivery == Delivery::Event {
+                vec![]
+            } else {
+                vec![serde_json::json!({ "event": "link-duplicate", "payload": payload })]
+            };
+            assert_eq!(*events.lock().unwrap(), expected_events, "{delivery:?}");
+            assert_eq!(*messages.lock().unwrap(), expected_messages, "{delivery:?}");
+        }
+    }
+
+    #[test]
+    fn field_naming_respells_nested_keys() {
+        let metadata = LinkMetadata {
+            url: "https://example.com/".to_string(),
+            canonical_url: Some("https://example.com/canonical".to_string()),
+            icons: vec![IconCandidate {
+                url: "https://example.com/icon.png".to_string(),
+                rel: "icon".to_string(),
+                sizes: None,
+                mime_type: Some("image/png".to_string()),
+                dimensions: None,
+            }],
+            ..Default::default()
+        };
+        let payload = serde_json::to_value(DuplicateLink { url: "u".to_string(), batch_id: 3 }).unwrap();
+        let value = serde_json::json!({ "batch_id": 3, "metadata": metadata });
+
+        assert_eq!(FieldNaming::SnakeCase.apply(value.clone()), value);
+        let camel = FieldNaming::CamelCase.apply(value);
+        assert_eq!(camel["batchId"], 3);
+        assert_eq!(camel["metadata"]["canonicalUrl"], "https://example.com/canonical");
+        assert_eq!(camel["metadata"]["originalImage"], serde_json::Value::Null);
+        assert_eq!(camel["metadata"]["icons"][0]["type"], "image/png");
+        assert!(camel["metadata"].get("canonical_url").is_none());
+        assert_eq!(FieldNaming::CamelCase.apply(payload), serde_json::json!({ "url": "u", "batchId": 3 }));
+    }
+
+    #[test]
+    fn field_naming_leaves_app_data_alone() {
+        let metadata = LinkMetadata {
+            url: "https://example.com/".to_string(),
+            sources: HashMap::from([("embed_html".to_string(), "oembed".to_string())]),
+            ..Default::default()
+        };
+        let error = DropError {
+            path: PathBuf::from("link.url"),
+            url: None,
+            batch_id: 1,
+            error: serde_json::json!({ "user_message": "Try again", "retry_after": { "wait_ms": 500 } }),
+        };
+
+        let camel = FieldNaming::CamelCase.apply(serde_json::json!({ "metadata": metadata }));
+        assert_eq!(camel["metadata"]["sources"], serde_json::json!({ "embed_html": "oembed" }));
+        let camel = FieldNaming::CamelCase.apply(serde_json::to_value(error).unwrap());
+        assert_eq!(camel["batchId"], 1);
+        assert_eq!(
+            camel["error"],
+            serde_json::json!({ "user_message": "Try again", "retry_after": { "wait_ms": 500 } })
+        );
+    }
+
+    #[tokio::test]
+    async fn trickling_body_hits_the_deadline() {
+        const TRICKLE: &[&str] = &["<"; 100];
+        let head = "HTTP/1.1 200 OK\r\ncontent-type: text/html\r\ncontent-length: 100\r\n\r\n";
+        let url = raw_server(head, TRICKLE, Duration::from_millis(100)).await;
+        let state = linkdrop(Builder::new().deadline(Duration::from_millis(500)));
+        let started = Instant::now();
+        let error = resolve_link(&state, &url).await.unwrap_err();
+        assert!(matches!(error, LinkdropError::Timeout(deadline) if deadline == Duration::from_millis(500)));
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn url_files_prefer_the_internet_shortcut_section() {
+        let dir = tempfile::tempdir().unwrap();
+        let sections = dir.path().join("sections.url");
+        fs::write(
+            &sections,
+            "[{000214A0-0000-0000-C000-000000000046}]\r\nURL=https://wrong.example/\r\n\
+             [InternetShortcut]\r\nIconIndex=0\r\nURL=https://right.example/\r\n",
+        )
+        .unwrap();
+        assert_eq!(parse_shortcut(&sections).unwrap(), "https://right.example/");
+
+        let headerless = dir.path().join("headerless.url");
+        fs::write(&headerless, "URL=https://bare.example/\n").unwrap();
+        assert_eq!(parse_shortcut(&headerless).unwrap(), "https://bare.example/");
+    }
+
+    #[test]
+    fn shortcut_urls_lose_trailing_junk() {
+        let dir = tempfile::tempdir().unwrap();
+        let nul = dir.path().join("nul.url");
+        fs::write(&nul, "[InternetShortcut]\nURL=https://example.com/page\0\0\r\n").unwrap();
+        assert_eq!(parse_shortcut(&nul).unwrap(), "https://example.com/page");
+
+        let garbage = dir.path().join("garbage.url");
+        fs::write(&garbage, "[InternetShortcut]\nURL=not a url\n").unwrap();
+        assert!(matches!(parse_shortcut(&garbage), Err(LinkdropError::ParseFailed(_))));
+        let blank = dir.path().join("blank.url");
+        fs::write(&blank, "[InternetShortcut]\nURL=\0 \n").unwrap();
+        assert!(matches!(parse_shortcut(&blank), Err(LinkdropError::ParseFailed(_))));
+    }
+
+    #[tokio::test]
+    async fn drop_handler_reads_each_shortcut_format() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET")).respond_with(page("<title>Dropped</title>")).mount(&server).await;
+        let url = format!("{}/page", server.uri());
+        let dir = tempfile::tempdir().unwrap();
+        let windows = shortcut(dir.path(), "page.url", &url);
+        let mac = dir.path().join("page.webloc");
+        fs::write(
+            &mac,
+            format!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<plist version="1.0"><dict><key>URL</key><string>{url}</string></dict></plist>"#
+            ),
+        )
+        .unwrap();
+        let text = dir.path().join("page.txt");
+        fs::write(&text, format!("\n  {url}\nsome notes\n")).unwrap();
+        let other = dir.path().join("page.pdf");
+        fs::write(&other, "%PDF-1.7").unwrap();
+
+        let handler = Builder::new().txt_links(true).drop_handler().unwrap();
+        for path in [&windows, &mac, &text] {
+            let event = handler.handle_drop(path).await.unwrap_or_else(|| panic!("{path:?}"));
+            assert_eq!(event.metadata.url, url);
+            assert_eq!(event.metadata.title.as_deref(), Some("Dropped"));
+            assert_eq!(&event.path, path);
+            assert_eq!(event.batch_total, 1);
+        }
+        assert!(handler.handle_drop(&other).await.is_none());
+        assert!(handler.handle_drop(&dir.path().join("missing.url")).await.is_none());
+
+        let handler = Builder::new().drop_handler().unwrap();
+        assert!(handler.handle_drop(&text).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn one_gesture_yields_one_link_per_distinct_url() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET")).respond_with(page("<title>Page</title>")).mount(&server).await;
+        let url = format!("{}/page", server.uri());
+        let dir = tempfile::tempdir().unwrap();
+        let mac = dir.path().join("page.webloc");
+        fs::write(&mac, format!("<plist><dict><key>URL</key><string>{url}</string></dict></plist>")).unwrap();
+        let text = dir.path().join("page.txt");
+        fs::write(&text, format!("{url}\n")).unwrap();
+        let other = shortcut(dir.path(), "other.url", &format!("{}/other", server.uri()));
+        let app = mock_app(Builder::new().txt_links(true).coalesce_window(Duration::from_millis(10)));
+        let dropped = record(&app, "link-dropped");
+        let duplicates = record(&app, "link-duplicate");
+        queue_drop(app.handle(), &[mac, text, other], PhysicalPosition::new(0.0, 0.0));
+
+        let mut urls: Vec<_> = wait_for(&dropped, 2).await.iter().map(|e| e["metadata"]["url"].clone()).collect();
+        urls.sort_by_key(|url| url.to_string());
+        assert_eq!(urls, [format!("{}/other", server.uri()), url.clone()]);
+        let duplicate = wait_for(&duplicates, 1).await.remove(0);
+        assert_eq!(duplicate["url"], url);
+        assert_eq!(duplicate["batch_id"], dropped.lock().unwrap()[0]["batch_id"]);
+    }
+
+    #[tokio::test]
+    async fn links_without_required_fields_are_held_back() {
+        let server = MockServer::start().await;
+        Mock::given(path("/titled")).respond_with(page("<title>Titled</title>")).mount(&server).await;
+        Mock::given(path("/bare")).respond_with(page("<p>No title here</p>")).mount(&server).await;
+        let dir = tempfile::tempdir().unwrap();
+        let titled = shortcut(dir.path(), "titled.url", &format!("{}/titled", server.uri()));
+        let bare = shortcut(dir.path(), "bare.url", &format!("{}/bare", server.uri()));
+
+        let handler = Builder::new().require_fields(Fields::TITLE, Insufficient::Suppress).drop_handler().unwrap();
+        assert!(handler.handle_drop(&titled).await.is_some());
+        assert!(handler.handle_drop(&bare).await.is_none());
+        assert!(Builder::new().drop_handler().unwrap().handle_drop(&bare).await.is_some());
+
+        let builder = Builder::new().require_fields(Fields::TITLE, Insufficient::Report);
+        let app = mock_app(builder.coalesce_window(Duration::from_millis(10)));
+        let dropped = record(&app, "link-dropped");
+        let insufficient = record(&app, "link-drop-insufficient");
+        queue_drop(app.handle(), &[titled, bare], PhysicalPosition::new(0.0, 0.0));
+        assert_eq!(wait_for(&dropped, 1).await[0]["metadata"]["title"], "Titled");
+        let held = wait_for(&insufficient, 1).await.remove(0);
+        assert_eq!(held["metadata"]["url"], format!("{}/bare", server.uri()));
+        assert_eq!(held["metadata"]["title"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn directory_walk_is_bounded() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("sub/deeper")).unwrap();
+        for name in ["a.url", "b.url", "sub/c.url", "sub/deeper/d.url"] {
+            shortcut(root, name, "https://example.com/");
+        }
+        fs::write(root.join("notes.pdf"), "%PDF").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(root, root.join("loop")).unwrap();
+        let origin = PhysicalPosition::new(1.0, 2.0);
+        let found = |builder: Builder| -> Vec<PathBuf> {
+            let expanded = expand_directories(&builder.drop, vec![(root.to_path_buf(), origin)]);
+            expanded.into_iter().map(|(path, _)| path.strip_prefix(root).unwrap().to_path_buf()).collect()
+        };
+
+        assert_eq!(found(Builder::new().directory_drops(1, 10)), ["a.url", "b.url", "sub/c.url"].map(PathBuf::from));
+        assert_eq!(found(Builder::new().directory_drops(0, 10)), ["a.url", "b.url"].map(PathBuf::from));
+        assert_eq!(found(Builder::new().directory_drops(5, 3)), ["a.url", "b.url", "sub/c.url"].map(PathBuf::from));
+        assert_eq!(found(Builder::new()), [PathBuf::new()]);
+    }
+
+    #[tokio::test]
+    async fn dropped_directory_processes_each_shortcut_in_one_batch() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET")).respond_with(page("<title>Page</title>")).mount(&server).await;
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["one", "two", "three"] {
+            shortcut(dir.path(), &format!("{name}.url"), &format!("{}/{name}", server.uri()));
+        }
+        let app = mock_app(Builder::new().directory_drops(0, 10).coalesce_window(Duration::from_millis(10)));
+        let dropped = record(&app, "link-dropped");
+        queue_drop(app.handle(), &[dir.path().to_path_buf()], PhysicalPosition::new(5.0, 6.0));
+
+        let events = wait_for(&dropped, 3).await;
+        let mut urls: Vec<_> = events.iter().map(|e| e["metadata"]["url"].as_str().unwrap().to_string()).collect();
+        urls.sort();
+        assert_eq!(urls, ["one", "three", "two"].map(|name| format!("{}/{name}", server.uri())));
+        assert!(events.iter().all(|e| e["batch_id"] == events[0]["batch_id"] && e["batch_total"] == 3 && e["x"] == 5.0));
+    }
+
+    /// Drop one shortcut, then another `gap` later, under a coalescing
+    /// `window`; returns the batch ids of their `link-dropped` events in drop
+    /// order.
+    async fn batch_ids(window: Duration, gap: Duration) -> (serde_json::Value, serde_json::Value) {
+        let server = MockServer::start().await;
+        Mock::given(method("GET")).respond_with(page("<title>Page</title>")).mount(&server).await;
+        let dir = tempfile::tempdir().unwrap();
+        let first = shortcut(dir.path(), "first.url", &format!("{}/first", server.uri()));
+        let second = shortcut(dir.path(), "second.url", &format!("{}/second", server.uri()));
+        let app = mock_app(Builder::new().coalesce_window(window));
+        let dropped = record(&app, "link-dropped");
+        queue_drop(app.handle(), &[first], PhysicalPosition::new(0.0, 0.0));
+        tokio::time::sleep(gap).await;
+        queue_drop(app.handle(), &[second], PhysicalPosition::new(0.0, 0.0));
+
+        let events = wait_for(&dropped, 2).await;
+        let batch_of = |name: &str| {
+            let event = events.iter().find(|e| e["metadata"]["url"].as_str().unwrap().ends_with(name)).unwrap();
+            event["batch_id"].clone()
+        };
+        (batch_of("/first"), batch_of("/second"))
+    }
+
+    #[tokio::test]
+    async fn drops_within_the_window_share_a_batch() {
+        let (first, second) = batch_ids(Duration::from_millis(300), Duration::from_millis(30)).await;
+        assert_eq!(first, second);
+    }
+
+    #[tokio::test]
+    async fn drops_outside_the_window_get_their_own_batch() {
+        let (first, second) = batch_ids(Duration::from_millis(10), Duration::from_millis(300)).await;
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn coalesce_window_is_clamped() {
+        let app = mock_app(Builder::new().coalesce_window(Duration::from_secs(3600)));
+        let window = app.state::<Linkdrop>().coalesce_window_ms.load(Ordering::Relaxed);
+        assert_eq!(window, MAX_COALESCE_WINDOW.as_millis() as u64);
+        let app = mock_app(Builder::new().coalesce_window(Duration::from_millis(40)));
+        assert_eq!(app.state::<Linkdrop>().coalesce_window_ms.load(Ordering::Relaxed), 40);
+    }
+
+    #[tokio::test]
+    async fn vetoed_links_are_asked_about_once_and_never_fetched() {
+        use std::sync::atomic::AtomicUsize;
+        let server = MockServer::start().await;
+        Mock::given(method("GET")).respond_with(page("<title>Page</title>")).mount(&server).await;
+        let dir = tempfile::tempdir().unwrap();
+        let saved = shortcut(dir.path(), "saved.url", &format!("{}/saved", server.uri()));
+        let fresh = shortcut(dir.path(), "fresh.url", &format!("{}/fresh", server.uri()));
+        let asked = Arc::new(AtomicUsize::new(0));
+        let handler = |suppress: bool| {
+            let asked = asked.clone();
+            let builder = Builder::new().suppress_unfetched(suppress).should_fetch(move |url| {
+                asked.fetch_add(1, Ordering::Relaxed);
+                url.path() != "/saved"
+            });
+            builder.drop_handler().unwrap()
+        };
+
+        let event = handler(false).handle_drop(&saved).await.unwrap();
+        assert_eq!(event.metadata.url, format!("{}/saved", server.uri()));
+        assert_eq!(event.metadata.title, None);
+        assert_eq!(asked.swap(0, Ordering::Relaxed), 1);
+        assert!(handler(true).handle_drop(&saved).await.is_none());
+        assert_eq!(asked.swap(0, Ordering::Relaxed), 1);
+        assert!(server.received_requests().await.unwrap().is_empty());
+
+        let event = handler(true).handle_drop(&fresh).await.unwrap();
+        assert_eq!(event.metadata.title.as_deref(), Some("Page"));
+        assert_eq!(asked.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn dropped_invalid_url_still_makes_a_url_only_link() {
+        let dir = tempfile::tempdir().unwrap();
+        let hostless = shortcut(dir.path(), "hostless.url", "http://");
+        let handler = Builder::new().drop_handler().unwrap();
+        let event = handler.handle_drop(&hostless).await.expect("a URL-only link");
+        assert_eq!(event.metadata.url, "http://");
+        assert_eq!(event.metadata.title, None);
+    }
+
+    #[tokio::test]
+    async fn hostless_drop_reports_parse_failed_with_its_url() {
+        let dir = tempfile::tempdir().unwrap();
+        let hostless = shortcut(dir.path(), "hostless.url", "http://");
+        let app = mock_app(Builder::new().coalesce_window(Duration::from_millis(10)));
+        let errors = record(&app, "link-drop-error");
+        let dropped = record(&app, "link-dropped");
+        queue_drop(app.handle(), std::slice::from_ref(&hostless), PhysicalPosition::new(0.0, 0.0));
+
+        let error = wait_for(&errors, 1).await.remove(0);
+        assert_eq!(error["url"], "http://");
+        assert_eq!(error["error"]["code"], "parse_failed");
+        let event = wait_for(&dropped, 1).await.remove(0);
+        assert_eq!(event["metadata"]["url"], "http://");
+        assert!(matches!(parse_link("http:///"), Err(LinkdropError::ParseFailed(_))));
+    }
+
+    #[tokio::test]
+    async fn map_error_shapes_the_error_payload() {
+        let dir = tempfile::tempdir().unwrap();
+        let hostless = shortcut(dir.path(), "hostless.url", "http://");
+        let builder = Builder::new()
+            .coalesce_window(Duration::from_millis(10))
+            .map_error(|error| serde_json::json!({ "friendly": "That link looks broken", "kind": error.code() }));
+        let app = mock_app(builder);
+        let errors = record(&app, "link-drop-error");
+        queue_drop(app.handle(), &[hostless], PhysicalPosition::new(0.0, 0.0));
+
+        let error = wait_for(&errors, 1).await.remove(0);
+        assert_eq!(error["error"], serde_json::json!({ "friendly": "That link looks broken", "kind": "parse_failed" }));
+    }
+
+    #[tokio::test]
+    async fn oversize_shortcut_is_refused_unread() {
+        let dir = tempfile::tempdir().unwrap();
+        let huge = dir.path().join("huge.url");
+        let padding = "; padding\n".repeat(200_000);
+        fs::write(&huge, format!("[InternetShortcut]\nURL=https://example.com/\n{padding}")).unwrap();
+        assert!(matches!(parse_shortcut(&huge), Err(LinkdropError::ParseFailed(_))));
+
+        let small = shortcut(dir.path(), "small.url", "https://example.com/");
+        assert!(matches!(read_shortcut(&small, 16), Err(LinkdropError::ParseFailed(_))));
+
+        let app = mock_app(Builder::new().max_shortcut_size(16).coalesce_window(Duration::from_millis(10)));
+        let errors = record(&app, "link-drop-error");
+        queue_drop(app.handle(), &[small], PhysicalPosition::new(0.0, 0.0));
+        let error = wait_for(&errors, 1).await.remove(0);
+        assert_eq!(error["url"], serde_json::Value::Null);
+        assert_eq!(error["error"]["code"], "parse_failed");
+    }
+
+    #[tokio::test]
+    async fn on_response_sees_every_hop_and_failure() {
+        let server = MockServer::start().await;
+        Mock::given(path("/start"))
+            .respond_with(ResponseTemplate::new(302).insert_header("location", "/page"))
+            .mount(&server)
+            .await;
+        let html = "<title>Page</title>";
+        Mock::given(path("/page")).respond_with(page(html)).mount(&server).await;
+        let audits = Arc::new(Mutex::new(Vec::new()));
+        let sink = audits.clone();
+        let state = linkdrop(Builder::new().on_response(move |audit| sink.lock().unwrap().push(audit.clone())));
+
+        resolve_link(&state, &format!("{}/start", server.uri())).await.unwrap();
+        let seen = audits.lock().unwrap().clone();
+        assert_eq!(seen.len(), 3, "{seen:?}");
+        assert_eq!(seen[0].url, format!("{}/start", server.uri()));
+        assert_eq!((seen[0].status, seen[0].bytes), (Some(302), 0));
+        assert_eq!(seen[1].url, format!("{}/page", server.uri()));
+        assert_eq!((seen[1].status, seen[1].bytes), (Some(200), html.len() as u64));
+        assert!(seen[1].elapsed.is_some());
+        assert_eq!(seen[2].url, format!("{}/favicon.ico", server.uri()));
+        assert_eq!(seen[2].status, Some(404));
+
+        audits.lock().unwrap().clear();
+        assert!(resolve_link(&state, "http://127.0.0.1:9/").await.is_err());
+        let seen = audits.lock().unwrap().clone();
+        assert!(!seen.is_empty() && seen.iter().all(|audit| audit.status.is_none()), "{seen:?}");
+    }
+
+    #[tokio::test]
+    async fn media_modes_limit_requests_to_the_page() {
+        let server = MockServer::start().await;
+        let html = format!(
+            r#"<title>Photo</title><meta property="og:image" content="{}/photo.jpg">"#,
+            server.uri()
+        );
+        Mock::given(path("/page")).respond_with(page(&html)).mount(&server).await;
+        let url = format!("{}/page", server.uri());
+
+        let state = linkdrop(Builder::new().media(MediaMode::NoRequests));
+        let meta = resolve_link(&state, &url).await.unwrap();
+        assert_eq!(meta.image, Some(format!("{}/photo.jpg", server.uri())));
+        assert_eq!(meta.favicon, None);
+
+        let state = linkdrop(Builder::new().media(MediaMode::Omit));
+        let meta = resolve_link(&state, &url).await.unwrap();
+        assert_eq!(meta.title.as_deref(), Some("Photo"));
+        assert!(meta.image.is_none() && meta.images.is_empty());
+        assert!(meta.favicon.is_none());
+
+        let requests = server.received_requests().await.unwrap();
+        let paths: Vec<_> = requests.iter().map(|request| request.url.path()).collect();
+        assert_eq!(paths, ["/page", "/page"]);
+
+        resolve_link(&linkdrop(Builder::new()), &url).await.unwrap();
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests.last().unwrap().url.path(), "/favicon.ico");
+    }
+
+    #[tokio::test]
+    async fn head_only_extracts_a_large_page_from_its_head() {
+        let head = r#"<!doctype html><html><HEAD>
+            <title>Big page</title>
+            <meta name="description" content="Mostly body.">
+            <meta property="og:image" content="https://cdn.example.com/big.png">
+            <link rel="icon" href="/favicon.png">
+        </Head>"#;
+        let body = format!("{head}<body>{}</body></html>", "<p>filler paragraph</p>".repeat(400_000));
+        assert!(body.len() as u64 > DEFAULT_MAX_BODY_SIZE);
+        let server = MockServer::start().await;
+        Mock::given(path("/big")).respond_with(page(&body)).mount(&server).await;
+        let url = format!("{}/big", server.uri());
+        let read = Arc::new(Mutex::new(Vec::new()));
+        let sink = read.clone();
+        let builder = Builder::new().head_only(true).favicon_fallback(false);
+        let state = linkdrop(builder.on_response(move |audit| sink.lock().unwrap().push(audit.bytes)));
+
+        let meta = resolve_link(&state, &url).await.unwrap();
+        assert_eq!(meta.title.as_deref(), Some("Big page"));
+        assert_eq!(meta.description.as_deref(), Some("Mostly body."));
+        assert_eq!(meta.image.as_deref(), Some("https://cdn.example.com/big.png"));
+        assert_eq!(meta.favicon, Some(format!("{}/favicon.png", server.uri())));
+        assert_eq!(*read.lock().unwrap(), [head.len() as u64]);
+
+        let result = resolve_link(&linkdrop(Builder::new()), &url).await;
+        assert!(matches!(result, Err(LinkdropError::TooLarge { .. })));
+    }
+
+    #[tokio::test]
+    async fn head_only_refuses_a_large_page_without_a_head() {
+        let body = format!("<title>No head</title>{}", "<p>filler paragraph</p>".repeat(100));
+        let server = MockServer::start().await;
+        Mock::given(path("/headless")).respond_with(page(&body)).mount(&server).await;
+        let url = format!("{}/headless", server.uri());
+        let builder = |limit| Builder::new().head_only(true).favicon_fallback(false).max_body_size(limit);
+
+        let result = resolve_link(&linkdrop(builder(1000)), &url).await;
+        assert!(
+            matches!(result, Err(LinkdropError::TooLarge { limit: 1000, length }) if length == body.len() as u64),
+            "{result:?}"
+        );
+        let meta = resolve_link(&linkdrop(builder(10_000)), &url).await.unwrap();
+        assert_eq!(meta.title.as_deref(), Some("No head"));
+    }
+
+    #[test]
+    fn head_end_finds_a_tag_split_across_chunks() {
+        let body = b"<head><title>t</title></HE";
+        assert_eq!(head_end(body, 0), None);
+        let scan_from = body.len() - (HEAD_CLOSE.len() - 1);
+        let body = [&body[..], b"AD><body>"].concat();
+        assert_eq!(head_end(&body, scan_from), Some(body.len() - "<body>".len()));
+    }
+
+    #[tokio::test]
+    async fn field_selectors_fill_in_nonstandard_markup() {
+        let server = MockServer::start().await;
+        let html = r#"<body>
+            <div class="doc-header"><h2>  Internal   design doc </h2></div>
+            <div class="hero" data-src="/img/hero.png"></div>
+        </body>"#;
+        Mock::given(path("/doc")).respond_with(page(html)).mount(&server).await;
+        let builder = Builder::new()
+            .field_selector(Fields::TITLE, ".missing", None)
+            .field_selector(Fields::TITLE, ".doc-header h2", None)
+            .field_selector(Fields::IMAGE, "div.hero", Some("data-src"));
+        let meta = resolve_link(&linkdrop(builder), &format!("{}/doc", server.uri())).await.unwrap();
+        assert_eq!(meta.title.as_deref(), Some("Internal design doc"));
+        assert_eq!(meta.image, Some(format!("{}/img/hero.png", server.uri())));
+    }
+
+    #[test]
+    fn invalid_field_selector_fails_setup() {
+        let error = Builder::new().field_selector(Fields::TITLE, "h2[", None).drop_handler().err().unwrap();
+        assert!(error.to_string().starts_with("invalid field selector `h2[`"), "{error}");
+    }
+
+    #[test]
+    fn proxy_template_gets_width_and_encoded_url() {
+        assert_eq!(
+            proxy_image("https://img.example.com/{width}/{url}", 400, "https://cdn.example.com/a b.png?w=1&h=2"),
+            "https://img.example.com/400/https%3A%2F%2Fcdn.example.com%2Fa%20b.png%3Fw%3D1%26h%3D2"
+        );
+    }
+
+    #[tokio::test]
+    async fn image_proxy_rewrites_only_the_image() {
+        let server = MockServer::start().await;
+        let html = r#"<meta property="og:image" content="https://cdn.example.com/card.png">"#;
+        Mock::given(path("/page")).respond_with(page(html)).mount(&server).await;
+        let url = format!("{}/page", server.uri());
+
+        let state = linkdrop(Builder::new().image_proxy("https://img.example.com/resize?w={width}&src={url}", 320));
+        let meta = resolve_link(&state, &url).await.unwrap();
+        assert_eq!(
+            meta.image.as_deref(),
+            Some("https://img.example.com/resize?w=320&src=https%3A%2F%2Fcdn.example.com%2Fcard.png")
+        );
+        assert_eq!(meta.original_image.as_deref(), Some("https://cdn.example.com/card.png"));
+        assert_eq!(meta.images, ["https://cdn.example.com/card.png"]);
+
+        let meta = resolve_link(&linkdrop(Builder::new()), &url).await.unwrap();
+        assert_eq!(meta.image.as_deref(), Some("https://cdn.example.com/card.png"));
+        assert_eq!(meta.original_image, None);
+    }
+
+    #[tokio::test]
+    async fn image_proxy_skips_data_urls() {
+        const PIXEL: &str = "data:image/gif;base64,R0lGODlhAQABAAAAACw=";
+        let server = MockServer::start().await;
+        let html = format!(r#"<meta property="og:image" content="{PIXEL}">"#);
+        Mock::given(path("/page")).respond_with(page(&html)).mount(&server).await;
+
+        let state = linkdrop(Builder::new().image_proxy("https://img.example.com/{width}/{url}", 320));
+        let meta = resolve_link(&state, &format!("{}/page", server.uri())).await.unwrap();
+        assert_eq!(meta.image.as_deref(), Some(PIXEL));
+        assert_eq!(meta.original_image, None);
+    }
+
+    #[test]
+    fn redirect_filter_sees_each_hop() {
+        let filter: RedirectFilter = Arc::new(|from: &Url, to: &Url| !(from.scheme() == "https" && to.scheme() == "http"));
+        let url = |url: &str| Url::parse(url).unwrap();
+        let previous = [url("http://example.com/"), url("https://example.com/secure")];
+
+        let refused = refused_redirect(&filter, &previous, &url("http://example.com/plain")).unwrap();
+        assert_eq!(refused.0, ["http://example.com/", "https://example.com/secure", "http://example.com/plain"]);
+        assert!(refused_redirect(&filter, &previous, &url("https://example.com/still-secure")).is_none());
+        assert!(refused_redirect(&filter, &previous[..1], &url("https://example.com/upgrade")).is_none());
+    }
+
+    #[tokio::test]
+    async fn refused_redirect_fails_with_the_chain() {
+        let server = MockServer::start().await;
+        let elsewhere = format!("http://localhost:{}/elsewhere", server.address().port());
+        Mock::given(path("/start"))
+            .respond_with(ResponseTemplate::new(302).insert_header("location", elsewhere.as_str()))
+            .mount(&server)
+            .await;
+        Mock::given(path("/elsewhere")).respond_with(page("<title>Elsewhere</title>")).mount(&server).await;
+        let start = format!("{}/start", server.uri());
+
+        let same_host = |from: &Url, to: &Url| from.host_str() == to.host_str();
+        let result = resolve_link(&linkdrop(Builder::new().redirect_filter(same_host)), &start).await;
+        assert!(
+            matches!(&result, Err(LinkdropError::RedirectRefused { chain }) if *chain == [start.clone(), elsewhere.clone()]),
+            "{result:?}"
+        );
+        let meta = resolve_link(&linkdrop(Builder::new()), &start).await.unwrap();
+        assert_eq!(meta.final_url, Some(elsewhere));
+    }
+
+    #[tokio::test]
+    async fn www_fallback_retries_the_other_host() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET")).respond_with(page("<title>Via www</title>")).mount(&server).await;
+        let port = server.address().port();
+        // Nothing listens on 127.0.0.2, so the bare host refuses connections.
+        let resolved = || {
+            Builder::new()
+                .resolve("example.test", std::net::SocketAddr::from(([127, 0, 0, 2], port)))
+                .resolve("www.example.test", *server.address())
+        };
+        let url = format!("http://example.test:{port}/article");
+
+        let meta = resolve_link(&linkdrop(resolved().www_fallback(true)), &url).await.unwrap();
+        assert_eq!(meta.title.as_deref(), Some("Via www"));
+        assert_eq!(meta.url, url);
+        assert_eq!(meta.final_url, Some(format!("http://www.example.test:{port}/article")));
+
+        let result = resolve_link(&linkdrop(resolved()), &url).await;
+        assert!(matches!(result, Err(LinkdropError::Http(e)) if e.is_connect()));
+        let denied = resolved().www_fallback(true).deny_host("www.example.test");
+        assert!(resolve_link(&linkdrop(denied), &url).await.is_err());
+    }
+
+    #[test]
+    fn toggle_www_adds_or_removes_the_prefix() {
+        let toggled = |url: &str| toggle_www(&Url::parse(url).unwrap()).map(String::from);
+        assert_eq!(toggled("https://example.com/a?b"), Some("https://www.example.com/a?b".to_string()));
+        assert_eq!(toggled("https://www.example.com/"), Some("https://example.com/".to_string()));
+        assert_eq!(toggled("http://127.0.0.1/"), None);
+        assert_eq!(toggled("http://[::1]/"), None);
+    }
+
+    #[tokio::test]
+    async fn mirrors_are_tried_until_one_succeeds() {
+        let server = MockServer::start().await;
+        Mock::given(path("/mirror")).respond_with(page("<title>Mirror</title>")).mount(&server).await;
+        let mirror = format!("{}/mirror", server.uri());
+        let client = Client::new();
+        let meta = fetch_metadata_mirrored(&client, &["http://127.0.0.1:9/", &mirror]).await.unwrap();
+        assert_eq!(meta.url, mirror);
+        assert_eq!(meta.title.as_deref(), Some("Mirror"));
+        assert!(fetch_metadata_mirrored(&client, &["http://127.0.0.1:9/"]).await.is_err());
+    }
+}
@@ -0,0 +1,76 @@
+use std::{
+    future::Future,
+    io,
+    pin::Pin,
+    sync::{
+        mpsc::{sync_channel, SyncSender},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+type Job = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// A fixed set of threads that each drive one fetch at a time, fed from a
+/// bounded queue.
+pub(crate) struct WorkerPool {
+    jobs: SyncSender<Job>,
+}
+
+impl WorkerPool {
+    pub fn new(size: usize) -> io::Result<Self> {
+        let size = size.max(1);
+        let (jobs, queue) = sync_channel::<Job>(size);
+        let queue = Arc::new(Mutex::new(queue));
+        for i in 0..size {
+            let queue = queue.clone();
+            thread::Builder::new()
+                .name(format!("linkdrop-worker-{i}"))
+                .spawn(move || loop {
+                    let job = queue.lock().unwrap().recv();
+                    match job {
+                        Ok(job) => tauri::async_runtime::block_on(job),
+                        Err(_) => break,
+                    }
+                })?;
+        }
+        Ok(Self { jobs })
+    }
+
+    /// Queue `job`, blocking the caller while the queue is full.
+    pub fn submit(&self, job: impl Future<Output = ()> + Send + 'static) {
+        let _ = self.jobs.send(Box::pin(job));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use super::*;
+
+    #[test]
+    fn pool_size_bounds_simultaneous_jobs() {
+        let pool = WorkerPool::new(2).unwrap();
+        let running = Arc::new(AtomicUsize::new(0));
+        let most = Arc::new(AtomicUsize::new(0));
+        let (done, finished) = std::sync::mpsc::channel();
+        for _ in 0..6 {
+            let (running, most, done) = (running.clone(), most.clone(), done.clone());
+            pool.submit(async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                most.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                done.send(()).unwrap();
+            });
+        }
+        for _ in 0..6 {
+            finished.recv_timeout(Duration::from_secs(5)).unwrap();
+        }
+        assert_eq!(most.load(Ordering::SeqCst), 2);
+    }
+}
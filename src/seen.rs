@@ -0,0 +1,47 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::Mutex,
+};
+
+#[derive(Default)]
+struct Inner {
+    urls: HashSet<String>,
+    /// Insertion order, for evicting the oldest URL once `limit` is reached.
+    order: VecDeque<String>,
+}
+
+/// URLs already handled this session.
+pub(crate) struct SeenUrls {
+    limit: Option<usize>,
+    inner: Mutex<Inner>,
+}
+
+impl SeenUrls {
+    pub fn new(limit: Option<usize>) -> Self {
+        Self {
+            limit,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    /// Mark `url` as seen, returning `false` if it already was.
+    pub fn insert(&self, url: &str) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        if !inner.urls.insert(url.to_string()) {
+            return false;
+        }
+        inner.order.push_back(url.to_string());
+        if self.limit.is_some_and(|limit| inner.order.len() > limit) {
+            if let Some(oldest) = inner.order.pop_front() {
+                inner.urls.remove(&oldest);
+            }
+        }
+        true
+    }
+
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.urls.clear();
+        inner.order.clear();
+    }
+}